@@ -35,19 +35,18 @@ macro_rules! bench_s11n {
 
 #[cfg(feature = "dedup_benchmarks")]
 macro_rules! dedup_bench {
-    ($f:ident, $hasher:ty, $hasher_name:expr, $hash_size:expr, $msg_size:expr) => {
+    ($f:ident, $hasher:ty, $hasher_name:expr, $msg_size:expr) => {
         pub fn $f(c: &mut Criterion) {
             const MSG_SIZE: usize = $msg_size;
             let mut group = c.benchmark_group(format!(
                 "{} dedup queue with {} B messages",
                 $hasher_name, $msg_size
             ));
+            let hasher = <$hasher>::default();
             for &size in &[1024, 4096, 1024 * 16, 1024 * 32] {
                 let mut queue = CircularQueue::with_capacity(size);
                 for _ in 0..size {
-                    let mut msg_hash = [0u8; $hash_size];
-                    msg_hash.copy_from_slice(&<$hasher>::digest(&generate_random_data(MSG_SIZE)));
-                    queue.push(msg_hash);
+                    queue.push(hasher.hash(&generate_random_data(MSG_SIZE)));
                 }
 
                 if MSG_SIZE > 4_000_000 {
@@ -60,8 +59,7 @@ macro_rules! dedup_bench {
                 group.bench_function(BenchmarkId::from_parameter(size), |b| {
                     b.iter(|| {
                         let new_msg = generate_random_data($msg_size);
-                        let mut new_msg_hash = [0u8; $hash_size];
-                        new_msg_hash.copy_from_slice(&<$hasher>::digest(&new_msg));
+                        let new_msg_hash = hasher.hash(&new_msg);
 
                         if !queue.iter().any(|h| h == &new_msg_hash) {
                             queue.push(new_msg_hash);
@@ -77,19 +75,19 @@ macro_rules! dedup_bench {
 #[cfg(feature = "dedup_benchmarks")]
 mod dedup {
     use circular_queue::CircularQueue;
-    use concordium_node::test_utils::generate_random_data;
+    use concordium_node::{
+        connection::{MessageHasher, Sha256Hasher, XxHash64Hasher},
+        test_utils::generate_random_data,
+    };
     use criterion::{BenchmarkId, Criterion, Throughput};
-    use digest::Digest;
-    use sha2::Sha256;
     use std::time::Duration;
-    use twox_hash::XxHash64;
 
-    dedup_bench!(small_bench_dedup_xxhash64, XxHash64, "XxHash64", 8, 250);
-    dedup_bench!(small_bench_dedup_sha256, Sha256, "SHA256", 32, 250);
-    dedup_bench!(medium_bench_dedup_xxhash64, XxHash64, "XxHash64", 8, 1_048_576);
-    dedup_bench!(medium_bench_dedup_sha256, Sha256, "SHA256", 32, 1_048_576);
-    dedup_bench!(big_bench_dedup_xxhash64, XxHash64, "XxHash64", 8, 4_194_304);
-    dedup_bench!(big_bench_dedup_sha256, Sha256, "SHA256", 32, 4_194_304);
+    dedup_bench!(small_bench_dedup_xxhash64, XxHash64Hasher, "XxHash64", 250);
+    dedup_bench!(small_bench_dedup_sha256, Sha256Hasher, "SHA256", 250);
+    dedup_bench!(medium_bench_dedup_xxhash64, XxHash64Hasher, "XxHash64", 1_048_576);
+    dedup_bench!(medium_bench_dedup_sha256, Sha256Hasher, "SHA256", 1_048_576);
+    dedup_bench!(big_bench_dedup_xxhash64, XxHash64Hasher, "XxHash64", 4_194_304);
+    dedup_bench!(big_bench_dedup_sha256, Sha256Hasher, "SHA256", 4_194_304);
 }
 
 mod s11n {
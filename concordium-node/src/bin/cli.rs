@@ -152,6 +152,7 @@ async fn main() -> anyhow::Result<()> {
         regenesis_arc: regenesis_arc.clone(),
         notification_context,
         unsupported_update_context,
+        call_duration: Some(node.stats.consensus_call_duration.clone()),
     };
     let consensus = plugins::consensus::start_consensus_layer(
         &conf.cli.baker,
@@ -161,8 +162,18 @@ async fn main() -> anyhow::Result<()> {
     )?;
     info!("Consensus layer started");
 
+    let genesis_check = check_genesis_hash(
+        &read_or_die!(regenesis_arc.blocks),
+        conf.cli.baker.expected_genesis_hash.as_ref(),
+    );
+    if let Err(e) = genesis_check {
+        stop_consensus_layer(consensus);
+        return Err(e);
+    }
+
     // Start stats collecting which depend on querying consensus.
-    let consensus_collector = StatsConsensusCollector::new(consensus.clone())?;
+    let consensus_collector =
+        StatsConsensusCollector::new(consensus.clone(), Arc::clone(&node.stats.baking_committee))?;
     node.stats.registry.register(Box::new(consensus_collector))?;
 
     // A flag to record that the import was stopped by a signal handler.
@@ -250,6 +261,25 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Initiating shutdown.");
 
+    // Let the connections drain first, if configured
+    if conf.connection.drain_timeout > 0 {
+        node.begin_draining();
+        let draining_node = Arc::clone(&node);
+        let threshold = usize::from(conf.connection.drain_peer_threshold);
+        let deadline = std::time::Instant::now()
+            + std::time::Duration::from_secs(conf.connection.drain_timeout);
+        match tokio::task::spawn_blocking(move || draining_node.await_drained(threshold, deadline))
+            .await
+        {
+            Ok(true) => info!("The connections have drained."),
+            Ok(false) => warn!(
+                "The connections did not drain in time; {} will be force-closed.",
+                read_or_die!(node.connections()).len()
+            ),
+            Err(e) => error!("Could not wait for the connections to drain: {}", e),
+        }
+    }
+
     // Message rpc to shutdown first
     if let Some(task) = rpc_server_task {
         // Only attempt shut down the RPC server in case it is not running.
@@ -298,6 +328,14 @@ async fn main() -> anyhow::Result<()> {
     // Wait for the P2PNode to close
     node.join().context("The node thread panicked!")?;
 
+    // Push the last datapoints to the push gateway, if there is one
+    let stats = node.stats.clone();
+    match tokio::task::spawn_blocking(move || stats.flush_now()).await {
+        Ok(Err(e)) => error!("Could not flush the statistics: {:#}", e),
+        Err(e) => error!("Could not flush the statistics: {}", e),
+        Ok(Ok(())) => (),
+    }
+
     // Wait for the consensus queue threads to stop
     for consensus_queue_thread in consensus_queue_threads {
         consensus_queue_thread.join().expect("A consensus queue thread panicked");
@@ -371,7 +409,7 @@ fn instantiate_node(
     app_prefs: &mut config::AppPreferences,
     stats_export_service: Arc<StatsExportService>,
     regenesis_arc: Arc<Regenesis>,
-) -> anyhow::Result<(Arc<P2PNode>, TcpListener, Poll)> {
+) -> anyhow::Result<(Arc<P2PNode>, Option<TcpListener>, Poll)> {
     // If the node id is supplied on the command line (in the conf argument) use it.
     // Otherwise try to look it up from the persistent config.
     let node_id = match conf.common.id {
@@ -1,6 +1,6 @@
 //! Types related to identifying peers.
 
-use crate::{common::P2PNodeId, connection::ConnectionStats};
+use crate::{common::P2PNodeId, connection::ConnectionStats, network::Networks};
 use anyhow::bail;
 use byteorder::{ReadBytesExt, WriteBytesExt};
 use concordium_base::common::{Buffer, Deserial, Serial};
@@ -23,6 +23,105 @@ pub enum PeerType {
     Bootstrapper,
 }
 
+// The role-specific decisions of the event loop are gathered here, so that
+// the differences between a node and a bootstrapper are kept in one place.
+impl PeerType {
+    /// Whether a peer in this role processes incoming network packets; a
+    /// bootstrapper only serves peer lists and drops any packets it receives.
+    pub fn accepts_packets(self) -> bool {
+        match self {
+            PeerType::Node => true,
+            PeerType::Bootstrapper => false,
+        }
+    }
+
+    /// Whether a peer in this role should be sent broadcast packets.
+    pub fn receives_broadcasts(self) -> bool { self.accepts_packets() }
+
+    /// Whether a peer in this role volunteers a peer list right after a
+    /// successful handshake.
+    pub fn sends_peer_list_on_handshake(self) -> bool {
+        match self {
+            PeerType::Node => false,
+            PeerType::Bootstrapper => true,
+        }
+    }
+
+    /// Whether a peer in this role refuses incoming connections once the hard
+    /// connection limit is reached.
+    pub fn enforces_hard_connection_limit(self) -> bool {
+        match self {
+            PeerType::Node => true,
+            PeerType::Bootstrapper => false,
+        }
+    }
+
+    /// Whether a peer in this role keeps its number of peers within the
+    /// configured bounds, i.e. looks for more peers when it has too few,
+    /// drops some when it has too many and periodically re-bootstraps.
+    pub fn maintains_peer_count(self) -> bool {
+        match self {
+            PeerType::Node => true,
+            PeerType::Bootstrapper => false,
+        }
+    }
+
+    /// Whether a peer in this role measures the latencies of its connections
+    /// and keeps track of when they were last seen.
+    pub fn monitors_connections(self) -> bool {
+        match self {
+            PeerType::Node => true,
+            PeerType::Bootstrapper => false,
+        }
+    }
+
+    /// Whether peers in this role are contacted to bootstrap, so that the
+    /// handshakes with them and the peers they return are recorded in order to
+    /// rank them.
+    pub fn is_bootstrap_source(self) -> bool {
+        match self {
+            PeerType::Node => false,
+            PeerType::Bootstrapper => true,
+        }
+    }
+
+    /// Whether peers in this role are kept in the buckets and so included in
+    /// the peer lists that are sent out.
+    pub fn is_kept_in_buckets(self) -> bool {
+        match self {
+            PeerType::Node => true,
+            PeerType::Bootstrapper => false,
+        }
+    }
+
+    /// The time after which the bucket entries of a peer in this role expire,
+    /// given the periods configured for nodes and for bootstrappers.
+    pub fn bucket_entry_timeout(self, node_period: u64, bootstrapper_period: u64) -> u64 {
+        match self {
+            PeerType::Node => node_period,
+            PeerType::Bootstrapper => bootstrapper_period,
+        }
+    }
+
+    /// Whether a peer in this role can be configured to keep track of only
+    /// some of the networks of its peers.
+    pub fn restricts_tracked_networks(self) -> bool {
+        match self {
+            PeerType::Node => false,
+            PeerType::Bootstrapper => true,
+        }
+    }
+
+    /// The number of threads used to process the sockets of a peer in this
+    /// role, given the configured thread pool size.
+    pub fn socket_threads(self, thread_pool_size: usize) -> usize {
+        match self {
+            PeerType::Node => thread_pool_size,
+            PeerType::Bootstrapper => 1,
+        }
+    }
+}
+
 impl fmt::Display for PeerType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -210,17 +309,21 @@ impl Display for P2PPeer {
 #[derive(Debug)]
 pub struct PeerStats {
     /// The peer's self identifier. Only used for reporting.
-    pub self_id:        P2PNodeId,
-    pub addr:           SocketAddr,
-    pub external_port:  u16,
+    pub self_id:           P2PNodeId,
+    pub addr:              SocketAddr,
+    pub external_port:     u16,
     /// Our identifier for the remote peer.
-    pub local_id:       RemotePeerId,
-    pub peer_type:      PeerType,
-    pub latency:        u64,
-    pub msgs_sent:      u64,
-    pub msgs_received:  u64,
-    pub bytes_sent:     u64,
-    pub bytes_received: u64,
+    pub local_id:          RemotePeerId,
+    pub peer_type:         PeerType,
+    /// The networks the peer is a member of.
+    pub networks:          Networks,
+    pub latency:           u64,
+    pub msgs_sent:         u64,
+    pub msgs_received:     u64,
+    pub bytes_sent:        u64,
+    pub bytes_received:    u64,
+    /// The number of messages from the peer that were dropped as unwanted.
+    pub unwanted_messages: u64,
 }
 
 impl PeerStats {
@@ -231,6 +334,7 @@ impl PeerStats {
         addr: SocketAddr,
         external_port: u16,
         peer_type: PeerType,
+        networks: Networks,
         conn_stats: &ConnectionStats,
     ) -> PeerStats {
         PeerStats {
@@ -239,11 +343,13 @@ impl PeerStats {
             addr,
             external_port,
             peer_type,
+            networks,
             latency: conn_stats.get_latency(),
             msgs_sent: conn_stats.messages_sent.load(AtomicOrdering::Relaxed),
             msgs_received: conn_stats.messages_received.load(AtomicOrdering::Relaxed),
             bytes_sent: conn_stats.bytes_sent.load(AtomicOrdering::Relaxed),
             bytes_received: conn_stats.bytes_received.load(AtomicOrdering::Relaxed),
+            unwanted_messages: conn_stats.unwanted_messages.load(AtomicOrdering::Relaxed),
         }
     }
 
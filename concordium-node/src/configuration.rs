@@ -3,10 +3,12 @@
 use crate::{
     common::P2PNodeId,
     connection::DeduplicationHashAlgorithm,
-    network::{WireProtocolVersion, WIRE_PROTOCOL_VERSIONS},
+    network::{NetworkId, WireProtocolVersion, WIRE_PROTOCOL_VERSIONS},
+    utils::{IpRange, LogTarget},
 };
 use anyhow::{ensure, Context};
 use app_dirs2::*;
+use concordium_base::hashes::BlockHash;
 use preferences::{Preferences, PreferencesMap};
 use std::{
     fs::{File, OpenOptions},
@@ -72,8 +74,6 @@ pub const MAX_BOOTSTRAPPER_KEEP_ALIVE: u64 = 20_000;
 pub const MAX_PREHANDSHAKE_KEEP_ALIVE: u64 = 10_000;
 /// Maximum time (in s) a soft ban is in force.
 pub const SOFT_BAN_DURATION_SECS: u64 = 300;
-/// Maximum number of networks a peer can share
-pub const MAX_PEER_NETWORKS: usize = 20;
 /// Database subdirectory name
 pub const DATABASE_SUB_DIRECTORY_NAME: &str = "database-v4";
 
@@ -286,6 +286,13 @@ pub struct BakerConfig {
         env = "CONCORDIUM_NODE_CONSENSUS_GENESIS_DATA_FILE"
     )]
     pub genesis_data_file: PathBuf,
+    #[structopt(
+        long = "expected-genesis-hash",
+        help = "Refuse to start unless the hash of the genesis block given by the genesis data \
+                file matches this one.",
+        env = "CONCORDIUM_NODE_CONSENSUS_EXPECTED_GENESIS_HASH"
+    )]
+    pub expected_genesis_hash: Option<BlockHash>,
     #[structopt(
         long = "accounts-cache-size",
         help = "The maximum number of accounts that can be stored in accounts cache",
@@ -307,6 +314,29 @@ pub struct BakerConfig {
         env = "CONCORDIUM_NODE_BAKER_DECRYPT_CREDENTIALS"
     )]
     pub decrypt_baker_credentials: bool,
+    #[structopt(
+        long = "strict-baker-credentials-permissions",
+        help = "Refuse to start if the baker credentials file is accessible by users other than \
+                its owner, instead of only warning about it. Only has effect on Unix.",
+        env = "CONCORDIUM_NODE_BAKER_STRICT_CREDENTIALS_PERMISSIONS"
+    )]
+    pub strict_baker_credentials_permissions: bool,
+    #[structopt(
+        long = "baker-min-peers",
+        help = "Stop baking when the number of connected node peers stays below this value for \
+                `baker-min-peers-grace-period`, and resume once it recovers. By default baking \
+                does not depend on the number of peers.",
+        env = "CONCORDIUM_NODE_BAKER_MIN_PEERS"
+    )]
+    pub baker_min_peers: Option<u16>,
+    #[structopt(
+        long = "baker-min-peers-grace-period",
+        help = "The time in seconds the number of node peers has to stay below `baker-min-peers` \
+                before baking is stopped.",
+        default_value = "60",
+        env = "CONCORDIUM_NODE_BAKER_MIN_PEERS_GRACE_PERIOD"
+    )]
+    pub baker_min_peers_grace_period: u64,
     #[structopt(
         long = "modules-cache-size",
         help = "The maximum number of smart contract modules that can be stored in the module \
@@ -483,6 +513,30 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_CONNECTION_RELAY_BROADCAST_PERCENTAGE"
     )]
     pub relay_broadcast_percentage: f64,
+    #[structopt(
+        long = "broadcast-max-peers",
+        help = "Send each broadcasted message to at most this many randomly chosen peers in the \
+                network, relying on them to relay it further. By default all peers are used.",
+        env = "CONCORDIUM_NODE_CONNECTION_BROADCAST_MAX_PEERS"
+    )]
+    pub broadcast_max_peers: Option<u16>,
+    #[structopt(
+        long = "broadcast-rate-limit",
+        help = "The maximum number of broadcasts of its own consensus messages per second the \
+                node sends in each network. Broadcasts beyond the limit are dropped. Relayed \
+                broadcasts, finalization messages and records, and direct messages are not \
+                limited. By default broadcasts are not limited.",
+        env = "CONCORDIUM_NODE_CONNECTION_BROADCAST_RATE_LIMIT"
+    )]
+    pub broadcast_rate_limit: Option<u32>,
+    #[structopt(
+        long = "broadcast-high-water-mark",
+        help = "Skip peers with more than this many bytes waiting to be sent to them when \
+                broadcasting, relying on other peers to relay the broadcasts to them. By default \
+                broadcasts are sent to all peers.",
+        env = "CONCORDIUM_NODE_CONNECTION_BROADCAST_HIGH_WATER_MARK"
+    )]
+    pub broadcast_high_water_mark: Option<usize>,
     #[structopt(
         long = "connect-to",
         short = "c",
@@ -497,6 +551,13 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_CONNECTION_DISALLOW_MULTIPLE_PEERS_ON_SAME_IP"
     )]
     pub disallow_multiple_peers_on_ip: bool,
+    #[structopt(
+        long = "max-peers-per-subnet",
+        help = "Maximum number of peers (excluding bootstrappers) in the same subnet, i.e., the \
+                same /24 for IPv4 and /64 for IPv6 addresses. Unlimited if not set.",
+        env = "CONCORDIUM_NODE_CONNECTION_MAX_PEERS_PER_SUBNET"
+    )]
+    pub max_peers_per_subnet: Option<u16>,
     #[structopt(
         long = "dns-resolver",
         help = "DNS resolver to use",
@@ -526,6 +587,14 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_CONNECTION_BOOTSTRAPPING_INTERVAL"
     )]
     pub bootstrapping_interval: u64,
+    #[structopt(
+        long = "bootstrap-resolution-timeout",
+        help = "The time in seconds to wait for the addresses of the bootstrappers to be \
+                resolved. Bootstrapping proceeds with the ones resolved by then.",
+        default_value = "10",
+        env = "CONCORDIUM_NODE_CONNECTION_BOOTSTRAP_RESOLUTION_TIMEOUT"
+    )]
+    pub bootstrap_resolution_timeout: u64,
     #[structopt(
         long = "max-latency",
         help = "The maximum allowed connection latency in ms",
@@ -547,6 +616,103 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_CONNECTION_REQUESTS_BATCH_LIMIT"
     )]
     pub conn_requests_batch_limit: u16,
+    #[structopt(
+        long = "listen-backlog",
+        help = "The maximum number of incoming connections waiting to be accepted by the node. \
+                Further connection attempts are refused by the operating system. The effective \
+                value may be capped by the operating system. The default is the value the node \
+                has always used.",
+        default_value = "1024",
+        env = "CONCORDIUM_NODE_CONNECTION_LISTEN_BACKLOG"
+    )]
+    pub listen_backlog: u32,
+    #[structopt(
+        long = "max-pending-outbound-connections",
+        help = "Maximum number of outgoing connections that may be awaiting the handshake at the \
+                same time. Further connection attempts are delayed until earlier ones complete or \
+                time out.",
+        default_value = "32",
+        env = "CONCORDIUM_NODE_CONNECTION_MAX_PENDING_OUTBOUND_CONNECTIONS"
+    )]
+    pub max_pending_outbound_connections: u16,
+    #[structopt(
+        long = "drain-timeout",
+        help = "On shutdown, stop accepting and making new connections and wait up to this many \
+                seconds for the existing ones to drop. The default of 0 shuts down immediately.",
+        default_value = "0",
+        env = "CONCORDIUM_NODE_CONNECTION_DRAIN_TIMEOUT"
+    )]
+    pub drain_timeout: u64,
+    #[structopt(
+        long = "drain-peer-threshold",
+        help = "The number of remaining connections at which draining is considered complete.",
+        default_value = "0",
+        env = "CONCORDIUM_NODE_CONNECTION_DRAIN_PEER_THRESHOLD"
+    )]
+    pub drain_peer_threshold: u16,
+    #[structopt(
+        long = "max-broadcast-age",
+        help = "Drop broadcast packets that arrive more than this many milliseconds after the \
+                peer sent them. Peers stamp the packets they relay themselves, so this limits the \
+                delay of each hop. By default broadcasts are accepted regardless of their age.",
+        env = "CONCORDIUM_NODE_CONNECTION_MAX_BROADCAST_AGE"
+    )]
+    pub max_broadcast_age: Option<u64>,
+    #[structopt(
+        long = "bootstrapper-handshake-networks",
+        help = "The networks announced to bootstrappers in the handshake and in requests for \
+                peers, out of the enabled ones. By default all enabled networks are announced.",
+        env = "CONCORDIUM_NODE_CONNECTION_BOOTSTRAPPER_HANDSHAKE_NETWORKS",
+        use_delimiter = true
+    )]
+    pub bootstrapper_handshake_networks: Vec<u16>,
+    #[structopt(
+        long = "node-handshake-networks",
+        help = "The networks announced to other nodes in the handshake and in requests for peers, \
+                out of the enabled ones. By default all enabled networks are announced.",
+        env = "CONCORDIUM_NODE_CONNECTION_NODE_HANDSHAKE_NETWORKS",
+        use_delimiter = true
+    )]
+    pub node_handshake_networks: Vec<u16>,
+    #[structopt(
+        long = "outbound-only",
+        help = "Do not listen for incoming connections and only connect to the given and \
+                bootstrapped peers. Useful for nodes that can't be reached from the outside, e.g. \
+                behind NAT. The node advertises port 0, so its peers don't pass it on to others.",
+        env = "CONCORDIUM_NODE_CONNECTION_OUTBOUND_ONLY"
+    )]
+    pub outbound_only: bool,
+    #[structopt(
+        long = "allow-local-peer-addresses",
+        help = "Connect to loopback and link-local addresses received from peers. By default \
+                these are ignored, so that peers can't make the node connect to local services. \
+                Useful for testing.",
+        env = "CONCORDIUM_NODE_CONNECTION_ALLOW_LOCAL_PEER_ADDRESSES"
+    )]
+    pub allow_local_peer_addresses: bool,
+    #[structopt(
+        long = "blocked-peer-addresses",
+        help = "Ranges of addresses in CIDR notation, e.g. `10.0.0.0/8`, that the node never \
+                connects to when they are received from peers.",
+        env = "CONCORDIUM_NODE_CONNECTION_BLOCKED_PEER_ADDRESSES",
+        use_delimiter = true
+    )]
+    pub blocked_peer_addresses: Vec<IpRange>,
+    #[structopt(
+        long = "max-invalid-messages",
+        help = "Soft-ban and disconnect peers that send more than this many messages rejected by \
+                consensus within a single housekeeping interval (see `housekeeping-interval`). By \
+                default such peers are only logged.",
+        env = "CONCORDIUM_NODE_CONNECTION_MAX_INVALID_MESSAGES"
+    )]
+    pub max_invalid_messages: Option<u64>,
+    #[structopt(
+        long = "max-peer-networks",
+        help = "Reject the handshakes of peers that are in more than this many networks.",
+        default_value = "20",
+        env = "CONCORDIUM_NODE_CONNECTION_MAX_PEER_NETWORKS"
+    )]
+    pub max_peer_networks: usize,
     #[structopt(
         long = "catch-up-batch-limit",
         help = "The maximum batch size for a catch-up round.",
@@ -589,6 +755,15 @@ pub struct ConnectionConfig {
         env = "CONCORDIUM_NODE_CONNECTION_SOCKET_READ_SIZE"
     )]
     pub socket_read_size: usize,
+    #[structopt(
+        long = "socket-read-budget",
+        help = "The number of bytes of messages read from a single connection in one iteration of \
+                the event loop, before moving on to the other connections. A message that has \
+                been started is always read in full. By default each connection is read until no \
+                more data is available.",
+        env = "CONCORDIUM_NODE_CONNECTION_SOCKET_READ_BUDGET"
+    )]
+    pub socket_read_budget: Option<usize>,
     #[structopt(
         long = "linger-time",
         help = "Max seconds a socket may linger",
@@ -709,6 +884,39 @@ pub struct CommonConfig {
         env = "CONCORDIUM_NODE_LOG_CONFIG"
     )]
     pub log_config: Option<PathBuf>,
+    #[structopt(
+        long = "log-target",
+        help = "Where to write the logs [stderr|file|syslog]. Ignored if --log-config is used.",
+        default_value = "stderr",
+        env = "CONCORDIUM_NODE_LOG_TARGET"
+    )]
+    pub log_target: LogTarget,
+    #[structopt(
+        long = "log-file",
+        help = "Path of the log file. Required when the log target is 'file'.",
+        env = "CONCORDIUM_NODE_LOG_FILE"
+    )]
+    pub log_file: Option<PathBuf>,
+    #[structopt(
+        long = "log-file-max-size",
+        help = "Rotate the log file once it exceeds this size (in bytes).",
+        default_value = "104857600",
+        env = "CONCORDIUM_NODE_LOG_FILE_MAX_SIZE"
+    )]
+    pub log_file_max_size: u64,
+    #[structopt(
+        long = "log-file-max-age",
+        help = "Rotate the log file once it has been written to for this long (in seconds).",
+        env = "CONCORDIUM_NODE_LOG_FILE_MAX_AGE"
+    )]
+    pub log_file_max_age: Option<u64>,
+    #[structopt(
+        long = "log-file-keep",
+        help = "Number of rotated log files to keep.",
+        default_value = "5",
+        env = "CONCORDIUM_NODE_LOG_FILE_KEEP"
+    )]
+    pub log_file_keep: u32,
     #[structopt(
         long = "minimum-peers-bucket",
         help = "Minimum peers to keep in each bucket always",
@@ -802,6 +1010,15 @@ pub struct BootstrapperConfig {
         env = "CONCORDIUM_NODE_BOOTSTRAPPER_REGENESIS_BLOCK_HASHES_FILE"
     )]
     pub regenesis_block_hashes: Option<PathBuf>,
+    #[structopt(
+        long = "tracked-networks",
+        help = "The networks a bootstrapper keeps track of in its buckets. Peers' memberships of \
+                other networks, and requests to join them, are ignored. By default all networks \
+                are tracked.",
+        env = "CONCORDIUM_NODE_BOOTSTRAPPER_TRACKED_NETWORKS",
+        use_delimiter = true
+    )]
+    pub tracked_networks: Vec<u16>,
 }
 
 #[cfg(target_os = "macos")]
@@ -892,6 +1109,21 @@ pub fn parse_config() -> anyhow::Result<Config> {
         "Percentage of peers to relay broadcasted packets to, must be between 0.0 and 1.0"
     );
 
+    ensure!(
+        conf.connection.max_pending_outbound_connections > 0,
+        "The maximum number of pending outbound connections must be at least 1"
+    );
+
+    ensure!(
+        conf.connection.broadcast_max_peers != Some(0),
+        "The maximum number of peers to broadcast to must be at least 1"
+    );
+
+    ensure!(
+        conf.connection.broadcast_rate_limit != Some(0),
+        "The broadcast rate limit must be at least 1"
+    );
+
     ensure!(
         conf.cli.baker.maximum_block_size <= 4_000_000_000
             && ((f64::from(conf.cli.baker.maximum_block_size) * 0.9).ceil()) as u32
@@ -910,11 +1142,43 @@ pub fn parse_config() -> anyhow::Result<Config> {
         "Socket read size must be greater or equal to the write size"
     );
 
+    ensure!(
+        conf.connection.socket_read_budget != Some(0),
+        "The socket read budget must be at least 1"
+    );
+
+    ensure!(
+        conf.connection.max_peers_per_subnet != Some(0),
+        "The maximum number of peers per subnet must be at least 1"
+    );
+
     ensure!(
         conf.bootstrapper.wait_until_minimum_nodes as usize <= conf.bootstrapper.peer_list_size,
         "wait-until-minimum-nodes must be lower than or equal to peer-list-size"
     );
 
+    ensure!(
+        conf.common.log_target != LogTarget::File || conf.common.log_file.is_some(),
+        "--log-file must be given when the log target is 'file'"
+    );
+
+    ensure!(conf.common.log_file_max_size > 0, "The maximum size of the log file must be non-zero");
+    ensure!(
+        conf.common.log_file_max_age != Some(0),
+        "The maximum age of the log file must be non-zero"
+    );
+
+    for &id in conf
+        .common
+        .network_ids
+        .iter()
+        .chain(&conf.connection.bootstrapper_handshake_networks)
+        .chain(&conf.connection.node_handshake_networks)
+        .chain(&conf.bootstrapper.tracked_networks)
+    {
+        NetworkId::try_new(id)?;
+    }
+
     ensure!(
         conf.connection.max_normal_keep_alive
             >= conf.connection.housekeeping_interval * (KEEP_ALIVE_FACTOR as u64),
@@ -1,4 +1,4 @@
-use anyhow::bail;
+use anyhow::{bail, ensure, Context};
 use byteorder::{NetworkEndian, WriteBytesExt};
 use bytesize::ByteSize;
 use mio::net::TcpStream;
@@ -7,8 +7,12 @@ use noiseexplorer_xx::{
     noisesession::NoiseSession,
     types::Keypair,
 };
+use thiserror::Error;
 
-use crate::{configuration::PROTOCOL_MAX_MESSAGE_SIZE, p2p::maintenance::P2PNode};
+use crate::{
+    common::PeerType, configuration::PROTOCOL_MAX_MESSAGE_SIZE, network::NetworkMessage,
+    p2p::maintenance::P2PNode,
+};
 
 use std::{
     cmp,
@@ -32,6 +36,105 @@ pub const PSK: &[u8] = b"b6461bd246843f70ac1328401405b2b4e725994d7d144a75bff1a04
 /// The size of the initial socket write queue allocation.
 const WRITE_QUEUE_ALLOC: usize = 1024 * 1024;
 
+/// Raised when a peer announces a message with a length of zero. Such frames
+/// are never produced by well-behaved peers, so they are treated as a
+/// protocol violation and the connection is dropped.
+#[derive(Debug, Error)]
+#[error("The peer sent a zero-sized message")]
+pub struct ZeroSizedMessage;
+
+/// Appends a complete frame made from `input` to `output`, i.e. its length
+/// followed by the noise messages obtained by encrypting it in chunks with
+/// `encrypt`, using `noise_buffer` as scratch space. If any of the chunks fails
+/// to encrypt, the error is returned and a partial frame is left in `output`.
+/// Since the noise session has advanced for the chunks encrypted before, the
+/// connection can't be recovered then and has to be closed.
+pub(super) fn encrypt_frame(
+    input: &[u8],
+    noise_buffer: &mut [u8],
+    output: &mut VecDeque<u8>,
+    mut encrypt: impl FnMut(&mut [u8]) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let num_full_chunks = input.len() / NOISE_MAX_PAYLOAD_LEN;
+    let last_chunk_len = {
+        let rem = input.len() % NOISE_MAX_PAYLOAD_LEN;
+        if rem != 0 {
+            rem + MAC_LENGTH
+        } else {
+            0
+        }
+    };
+    let full_msg_len = num_full_chunks * NOISE_MAX_MESSAGE_LEN + last_chunk_len;
+
+    output.reserve(PAYLOAD_SIZE + full_msg_len);
+    output.extend(&(full_msg_len as PayloadSize).to_be_bytes());
+
+    for chunk in input.chunks(NOISE_MAX_PAYLOAD_LEN) {
+        let encrypted_len = chunk.len() + MAC_LENGTH;
+        noise_buffer[..chunk.len()].copy_from_slice(chunk);
+        encrypt(&mut noise_buffer[..encrypted_len])?;
+        output.extend(&noise_buffer[..encrypted_len]);
+    }
+
+    Ok(())
+}
+
+/// Check the length announced for an incoming message against the limits of
+/// the protocol, which are stricter while the noise handshake is in progress.
+fn check_message_size(expected_size: PayloadSize, is_post_handshake: bool) -> anyhow::Result<()> {
+    if expected_size == 0 {
+        bail!(ZeroSizedMessage);
+    }
+
+    if !is_post_handshake && expected_size >= HANDSHAKE_SIZE_LIMIT as u32 {
+        bail!(
+            "expected message size ({}) exceeds the handshake size limit ({})",
+            ByteSize(expected_size as u64).to_string_as(true),
+            ByteSize(HANDSHAKE_SIZE_LIMIT as u64).to_string_as(true),
+        );
+    }
+
+    // check if the expected size doesn't exceed the protocol limit
+    if expected_size > PROTOCOL_MAX_MESSAGE_SIZE {
+        bail!(
+            "expected message size ({}) exceeds the maximum protocol size ({})",
+            ByteSize(expected_size as u64).to_string_as(true),
+            ByteSize(PROTOCOL_MAX_MESSAGE_SIZE as u64).to_string_as(true)
+        );
+    }
+
+    Ok(())
+}
+
+/// Decodes a captured stream of plaintext frames, i.e. of messages as they
+/// are after decryption, each preceded by its length. The same size checks
+/// are applied as when reading from a connection, so that a problematic
+/// stream can be replayed deterministically without a socket. Decoding stops
+/// at the first invalid frame, including an incomplete one at the end.
+pub fn decode_plaintext_frames(mut bytes: &[u8]) -> anyhow::Result<Vec<NetworkMessage>> {
+    let mut messages = Vec::new();
+    while !bytes.is_empty() {
+        ensure!(bytes.len() >= PAYLOAD_SIZE, "incomplete length of frame {}", messages.len());
+        let (size_bytes, rest) = bytes.split_at(PAYLOAD_SIZE);
+        let expected_size = PayloadSize::from_be_bytes(size_bytes.try_into()?);
+        check_message_size(expected_size, true)
+            .with_context(|| format!("invalid frame {}", messages.len()))?;
+        ensure!(
+            rest.len() >= expected_size as usize,
+            "incomplete frame {}: expected {} bytes, but only {} remain",
+            messages.len(),
+            expected_size,
+            rest.len()
+        );
+        let (message, rest) = rest.split_at(expected_size as usize);
+        let message = NetworkMessage::deserialize(message)
+            .with_context(|| format!("invalid message in frame {}", messages.len()))?;
+        messages.push(message);
+        bytes = rest;
+    }
+    Ok(messages)
+}
+
 /// A single encrypted message currently being read from the socket.
 #[derive(Default)]
 struct IncomingMessage {
@@ -123,6 +226,8 @@ pub struct ConnectionLowLevel {
     is_initialized: bool,
     /// If specified, the linger value to set for the socket
     so_linger:      Option<u16>,
+    /// The expected type of the remote peer.
+    peer_type:      PeerType,
 }
 
 macro_rules! recv_xx_msg {
@@ -159,6 +264,7 @@ impl ConnectionLowLevel {
         handler: &Arc<P2PNode>,
         socket: TcpStream,
         is_initiator: bool,
+        peer_type: PeerType,
         read_size: usize,
         write_size: usize,
     ) -> Self {
@@ -189,9 +295,14 @@ impl ConnectionLowLevel {
             is_writable: false,
             is_initialized: false,
             so_linger,
+            peer_type,
         }
     }
 
+    /// Check whether we initiated the connection.
+    #[inline]
+    pub fn is_initiator(&self) -> bool { self.noise_session.is_initiator() }
+
     #[cfg(unix)]
     fn set_linger(&self, onoff: bool, linger_time: u16) {
         use libc::{c_int, c_void, linger, setsockopt, socklen_t, SOL_SOCKET, SO_LINGER};
@@ -286,7 +397,8 @@ impl ConnectionLowLevel {
         recv_xx_msg!(self, len, "A");
         let pad = 16;
         let payload_in = self.socket_buffer.slice(len)[DHLEN..][..len - DHLEN - pad].try_into()?;
-        let payload_out = self.handler.upgrade().unwrap().produce_handshake_request()?; // safe
+        let payload_out =
+            self.handler.upgrade().unwrap().produce_handshake_request(self.peer_type)?; // safe
         send_xx_msg!(self, DHLEN * 2 + MAC_LENGTH, &payload_out, MAC_LENGTH, "B");
 
         Ok(payload_in)
@@ -297,7 +409,8 @@ impl ConnectionLowLevel {
         let payload_in = self.socket_buffer.slice(len)[DHLEN * 2 + MAC_LENGTH..]
             [..len - DHLEN * 2 - MAC_LENGTH * 2]
             .try_into()?;
-        let payload_out = self.handler.upgrade().unwrap().produce_handshake_request()?; // safe
+        let payload_out =
+            self.handler.upgrade().unwrap().produce_handshake_request(self.peer_type)?; // safe
         send_xx_msg!(self, DHLEN + MAC_LENGTH, &payload_out, MAC_LENGTH, "C");
         self.socket.set_nodelay(false)?;
         Ok(payload_in)
@@ -344,6 +457,8 @@ impl ConnectionLowLevel {
                     self.socket_buffer.remaining = num_bytes;
                 }
                 Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(ReadResult::WouldBlock),
+                // nothing was read, so the read can simply be retried
+                Err(e) if e.kind() == ErrorKind::Interrupted => return Ok(ReadResult::Incomplete),
                 Err(e) => return Err(e.into()),
             }
         };
@@ -377,26 +492,7 @@ impl ConnectionLowLevel {
                 PayloadSize::from_be_bytes((&self.incoming_msg.size_bytes[..]).try_into()?);
             self.incoming_msg.size_bytes.clear();
 
-            if expected_size == 0 {
-                bail!("I got a zero-sized message");
-            }
-
-            if !self.is_post_handshake() && expected_size >= HANDSHAKE_SIZE_LIMIT as u32 {
-                bail!(
-                    "expected message size ({}) exceeds the handshake size limit ({})",
-                    ByteSize(expected_size as u64).to_string_as(true),
-                    ByteSize(HANDSHAKE_SIZE_LIMIT as u64).to_string_as(true),
-                );
-            }
-
-            // check if the expected size doesn't exceed the protocol limit
-            if expected_size > PROTOCOL_MAX_MESSAGE_SIZE {
-                bail!(
-                    "expected message size ({}) exceeds the maximum protocol size ({})",
-                    ByteSize(expected_size as u64).to_string_as(true),
-                    ByteSize(PROTOCOL_MAX_MESSAGE_SIZE as u64).to_string_as(true)
-                );
-            }
+            check_message_size(expected_size, self.is_post_handshake())?;
 
             trace!("Expecting a {} message", ByteSize(expected_size as u64).to_string_as(true));
             self.incoming_msg.pending_bytes = expected_size as usize;
@@ -518,6 +614,16 @@ impl ConnectionLowLevel {
         self.encrypt_and_enqueue(&input)
     }
 
+    /// Check whether at least a full write's worth of bytes is waiting to be
+    /// written to the socket, in which case further messages should be kept
+    /// in their priority queues rather than encrypted and enqueued behind it.
+    #[inline]
+    pub fn is_backlogged(&self) -> bool { self.output_queue.len() >= self.write_size() }
+
+    /// The number of bytes waiting to be written to the socket.
+    #[inline]
+    pub fn pending_write_bytes(&self) -> usize { self.output_queue.len() }
+
     /// Writes enequeued bytes to the socket until the queue is exhausted
     /// or the write would be blocking.
     #[inline]
@@ -557,14 +663,17 @@ impl ConnectionLowLevel {
             self.socket_buffer.buf[front_len..][..back_len].copy_from_slice(&back[..back_len]);
         }
 
-        let written = match self.socket.write(&self.socket_buffer.buf[..write_size]) {
-            Ok(num_bytes) => num_bytes,
-            Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                self.is_writable = false;
-                debug!("Sending would block (setting non-writable). {:?}", self.socket);
-                return Ok(0);
+        let written = loop {
+            match self.socket.write(&self.socket_buffer.buf[..write_size]) {
+                Ok(num_bytes) => break num_bytes,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    self.is_writable = false;
+                    debug!("Sending would block (setting non-writable). {:?}", self.socket);
+                    return Ok(0);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
             }
-            Err(e) => return Err(e.into()),
         };
 
         self.output_queue.drain(..written);
@@ -578,52 +687,26 @@ impl ConnectionLowLevel {
     }
 
     /// It encrypts `input` and enqueues the encrypted chunks preceded by the
-    /// length for later sending.
+    /// length for later sending. An encryption failure is fatal for the
+    /// connection; the error is returned before anything else is flushed, and
+    /// the connection is closed as for any other error.
     #[inline]
     fn encrypt_and_enqueue(&mut self, input: &[u8]) -> anyhow::Result<()> {
-        let num_full_chunks = input.len() / NOISE_MAX_PAYLOAD_LEN;
-        let last_chunk_len = {
-            let rem = input.len() % NOISE_MAX_PAYLOAD_LEN;
-            if rem != 0 {
-                rem + MAC_LENGTH
-            } else {
-                0
-            }
-        };
-        let full_msg_len = num_full_chunks * NOISE_MAX_MESSAGE_LEN + last_chunk_len;
-
-        self.output_queue.extend(&(full_msg_len as PayloadSize).to_be_bytes());
-
-        let mut input = Cursor::new(input);
-        let eof = input.get_ref().len() as u64;
-
-        while input.position() != eof {
-            self.encrypt_chunk(&mut input)?;
+        let session = &mut self.noise_session;
+        encrypt_frame(input, &mut self.noise_buffer, &mut self.output_queue, |msg| {
+            session.send_message(msg)?;
+            Ok(())
+        })?;
 
-            if self.output_queue.len() >= self.write_size() {
-                self.flush_socket_once()?;
+        while self.output_queue.len() >= self.write_size() {
+            if self.flush_socket_once()? == 0 {
+                break;
             }
         }
 
         Ok(())
     }
 
-    /// Produces and enqueues a single noise message from `input`, potentially
-    /// squeezing it with the previously enqueued chunk.
-    #[inline]
-    fn encrypt_chunk(&mut self, input: &mut Cursor<&[u8]>) -> anyhow::Result<()> {
-        let remaining_len = input.get_ref().len() - input.position() as usize;
-        let chunk_size = cmp::min(NOISE_MAX_PAYLOAD_LEN, remaining_len);
-        input.read_exact(&mut self.noise_buffer[..chunk_size])?;
-        let encrypted_len = chunk_size + MAC_LENGTH;
-
-        self.noise_session.send_message(&mut self.noise_buffer[..encrypted_len])?;
-
-        self.output_queue.extend(&self.noise_buffer[..encrypted_len]);
-
-        Ok(())
-    }
-
     /// Get the desired socket read size.
     #[inline]
     fn read_size(&self) -> usize { self.socket_buffer.buf.len() }
@@ -1,11 +1,8 @@
 //! Incoming network message handing.
 
 use crate::{
-    common::{
-        p2p_peer::{PeerStats, RemotePeerId},
-        PeerType,
-    },
-    configuration::{is_compatible_version, is_compatible_wire_version, MAX_PEER_NETWORKS},
+    common::p2p_peer::{PeerStats, RemotePeerId},
+    configuration::{is_compatible_version, is_compatible_wire_version},
     connection::{ConnChange, Connection},
     network::{
         Handshake, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest, NetworkResponse,
@@ -14,7 +11,30 @@ use crate::{
     plugins::consensus::*,
     read_or_die,
 };
-use anyhow::{bail, ensure};
+use anyhow::bail;
+use concordium_base::hashes::BlockHash;
+use thiserror::Error;
+
+/// Raised when the genesis block hashes announced by a peer in its handshake
+/// diverge from our own, i.e. when the peer is on a different chain.
+#[derive(Debug, Error)]
+#[error(
+    "Rejecting handshake: Didn't find a common prefix on the genesis block hashes. Difference: \
+     our block: {ours}, their block {theirs} at position {position}."
+)]
+pub struct GenesisMismatch {
+    pub ours:     BlockHash,
+    pub theirs:   BlockHash,
+    pub position: usize,
+}
+
+/// Raised when a peer announces more networks in its handshake than we accept.
+#[derive(Debug, Error)]
+#[error("Rejecting handshake: too many networks ({count}, at most {max} are accepted).")]
+pub struct TooManyNetworks {
+    pub count: usize,
+    pub max:   usize,
+}
 
 impl Connection {
     /// Processes a network message based on its type.
@@ -29,11 +49,13 @@ impl Connection {
                 return self.handle_handshake_req(handshake, conn_stats);
             }
             _ => {
-                ensure!(
-                    self.is_post_handshake(),
-                    "Connection to {} has not yet completed the handshake.",
-                    self.remote_peer.local_id
-                );
+                if !self.is_post_handshake() {
+                    self.register_unwanted_message("before handshake");
+                    bail!(
+                        "Connection to {} has not yet completed the handshake.",
+                        self.remote_peer.local_id
+                    );
+                }
                 self.remote_peer.local_id
             }
         };
@@ -57,11 +79,18 @@ impl Connection {
             }
             NetworkPayload::NetworkResponse(NetworkResponse::PeerList(peers), ..) => {
                 debug!("Got a PeerList ({} peers) from peer {}", peers.len(), peer_id);
+                if self.remote_peer.peer_type.is_bootstrap_source() {
+                    self.handler.register_bootstrapper_peers(self.remote_peer.addr, peers.len());
+                }
                 self.handler.register_conn_change(ConnChange::NewPeers(peers));
                 Ok(())
             }
             NetworkPayload::NetworkRequest(NetworkRequest::JoinNetwork(network), ..) => {
                 debug!("Got a JoinNetwork request from peer {}", peer_id);
+                if !self.is_tracked_network(network) {
+                    self.register_unwanted_message("untracked network");
+                    return Ok(());
+                }
                 self.add_remote_end_network(network)
             }
             NetworkPayload::NetworkRequest(NetworkRequest::LeaveNetwork(network), ..) => {
@@ -99,8 +128,11 @@ impl Connection {
                     handshake.wire_versions
                 );
             };
-        if handshake.networks.len() > MAX_PEER_NETWORKS {
-            bail!("Rejecting handshake: too many networks.");
+        if handshake.networks.len() > self.handler.config.max_peer_networks {
+            bail!(TooManyNetworks {
+                count: handshake.networks.len(),
+                max:   self.handler.config.max_peer_networks,
+            });
         }
 
         {
@@ -112,14 +144,13 @@ impl Connection {
                 .zip(handshake.genesis_blocks.iter())
                 .enumerate()
                 .find(|(_, (a, b))| a != b);
-            if let Some((i, (ours, theirs))) = common_blocks {
-                bail!(
-                    "Rejecting handshake: Didn't find a common prefix on the genesis block \
-                     hashes. Difference: our block: {}, their block {} at position {}.",
-                    ours,
-                    theirs,
-                    i
-                );
+            if let Some((position, (ours, theirs))) = common_blocks {
+                self.handler.stats.wrong_genesis_peers.inc();
+                bail!(GenesisMismatch {
+                    ours: *ours,
+                    theirs: *theirs,
+                    position,
+                });
             }
         }
 
@@ -130,7 +161,7 @@ impl Connection {
             wire_version,
         );
 
-        if self.handler.peer_type() == PeerType::Bootstrapper {
+        if self.handler.peer_type().sends_peer_list_on_handshake() {
             debug!("Running in bootstrapper mode; attempting to send a PeerList upon handshake");
             self.send_peer_list_resp(handshake.networks, conn_stats)?;
         }
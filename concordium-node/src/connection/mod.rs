@@ -19,22 +19,22 @@ use crate::{
         p2p_peer::{P2PPeer, PeerStats},
         P2PNodeId, PeerType, RemotePeer,
     },
-    configuration::MAX_PEER_NETWORKS,
-    connection::low_level::ReadResult,
+    connection::low_level::{ReadResult, ZeroSizedMessage},
     netmsg,
     network::{
         NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest, NetworkResponse,
         Networks, WireProtocolVersion, WIRE_PROTOCOL_CURRENT_VERSION,
     },
     p2p::P2PNode,
-    read_or_die, write_or_die,
+    read_or_die,
+    stats_export_service::NodeState,
+    write_or_die,
 };
 
 use crate::consensus_ffi::helpers::PacketType;
 
 use std::{
     collections::VecDeque,
-    convert::TryFrom,
     fmt,
     net::SocketAddr,
     ops::{Index, IndexMut},
@@ -48,7 +48,7 @@ use std::{
 /// Designates the sending priority of outgoing messages.
 // If a message is labelled as having `High` priority it is always pushed to the
 // front of the queue in the sinks when sending, and otherwise to the back.
-#[derive(PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 pub enum MessageSendingPriority {
     /// Queued FIFO-style.
     Normal,
@@ -86,25 +86,46 @@ pub trait DeduplicationQueue: Send + Sync {
     fn invalidate_if_exists(&mut self, input: &[u8]);
 }
 
-/// XxHash64 deduplication struct
-pub struct DeduplicationQueueXxHash64 {
-    /// Random seed generated per queue when constructed
-    seed:  u64,
-    /// The queue itself
-    queue: CircularQueue<u64>,
+/// A hash function for network messages, such as the one used for
+/// deduplication.
+pub trait MessageHasher: Send + Sync {
+    /// The type of the produced hashes.
+    type Hash: Copy + Eq + fmt::Debug + Send + Sync;
+
+    /// Hash an input given as a byte slice
+    fn hash(&self, input: &[u8]) -> Self::Hash;
+
+    /// The value an invalidated hash is replaced with.
+    fn invalidated(hash: Self::Hash) -> Self::Hash;
 }
 
-impl DeduplicationQueueXxHash64 {
-    /// Constructs a new XxHash64 deduplication queue with a random seed
-    pub fn new(capacity: usize) -> Self {
-        use rand::Rng;
+/// The XxHash64 message hasher; it is fast, but not cryptographically secure.
+pub struct XxHash64Hasher {
+    /// The seed of the hash function
+    seed: u64,
+}
+
+impl XxHash64Hasher {
+    /// Constructs a hasher with the given seed; the hashes of a message are
+    /// stable for as long as the seed is the same.
+    pub fn with_seed(seed: u64) -> Self {
         Self {
-            seed:  rand::thread_rng().gen::<u64>(),
-            queue: CircularQueue::with_capacity(capacity),
+            seed,
         }
     }
+}
+
+impl Default for XxHash64Hasher {
+    /// Constructs a hasher with a random seed
+    fn default() -> Self {
+        use rand::Rng;
+        Self::with_seed(rand::thread_rng().gen::<u64>())
+    }
+}
+
+impl MessageHasher for XxHash64Hasher {
+    type Hash = u64;
 
-    /// Hash an input given as a byte slice
     fn hash(&self, input: &[u8]) -> u64 {
         use std::hash::Hasher;
         use twox_hash::XxHash64;
@@ -112,74 +133,78 @@ impl DeduplicationQueueXxHash64 {
         hasher.write(input);
         hasher.finish()
     }
+
+    // flip all bits in the old hash
+    fn invalidated(hash: u64) -> u64 { !hash }
 }
 
-impl DeduplicationQueue for DeduplicationQueueXxHash64 {
-    fn check_and_insert(&mut self, input: &[u8]) -> anyhow::Result<bool> {
-        let num = self.hash(input);
-        if !self.queue.iter().any(|n| n == &num) {
-            trace!("Message XxHash64 {:x} is unique, adding to dedup queue", num);
-            self.queue.push(num);
-            Ok(false)
-        } else {
-            trace!("Message XxHash64 {:x} is a duplicate", num);
-            Ok(true)
-        }
-    }
+/// The SHA256 message hasher, for where collisions must be avoided.
+#[derive(Default)]
+pub struct Sha256Hasher;
 
-    fn invalidate_if_exists(&mut self, input: &[u8]) {
-        let num = self.hash(input);
-        if let Some(old_val) = self.queue.iter_mut().find(|val| **val == num) {
-            // flip all bits in the old hash; we can't just remove the value from the queue
-            *old_val = !*old_val;
-        }
+impl MessageHasher for Sha256Hasher {
+    type Hash = [u8; 32];
+
+    fn hash(&self, input: &[u8]) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(input).into()
     }
+
+    // zero out all bits in the old hash
+    fn invalidated(_hash: [u8; 32]) -> [u8; 32] { Default::default() }
 }
 
-/// SHA256 deduplication struct
-pub struct DeduplicationQueueSha256 {
+/// A deduplication queue of message hashes.
+pub struct HashDeduplicationQueue<H: MessageHasher> {
+    /// The hash function
+    hasher: H,
     /// The queue itself
-    queue: CircularQueue<[u8; 32]>,
+    queue:  CircularQueue<H::Hash>,
 }
 
-impl DeduplicationQueueSha256 {
-    /// Constructs a new SHA256 deduplication queue
-    pub fn new(capacity: usize) -> Self {
+impl<H: MessageHasher + Default> HashDeduplicationQueue<H> {
+    /// Constructs a new deduplication queue with the default hasher
+    pub fn new(capacity: usize) -> Self { Self::with_hasher(H::default(), capacity) }
+}
+
+impl<H: MessageHasher> HashDeduplicationQueue<H> {
+    /// Constructs a new deduplication queue with the given hasher
+    pub fn with_hasher(hasher: H, capacity: usize) -> Self {
         Self {
+            hasher,
             queue: CircularQueue::with_capacity(capacity),
         }
     }
-
-    /// Hash an input given as a byte slice
-    fn hash(&self, input: &[u8]) -> [u8; 32] {
-        use sha2::{Digest, Sha256};
-        Sha256::digest(input).into()
-    }
 }
 
-impl DeduplicationQueue for DeduplicationQueueSha256 {
+impl<H: MessageHasher> DeduplicationQueue for HashDeduplicationQueue<H> {
     fn check_and_insert(&mut self, input: &[u8]) -> anyhow::Result<bool> {
-        let hash = self.hash(input);
+        let hash = self.hasher.hash(input);
         if !self.queue.iter().any(|n| *n == hash) {
-            trace!("Message SHA256 {:X?} is unique, adding to dedup queue", &hash[..]);
+            trace!("Message hash {:X?} is unique, adding to dedup queue", hash);
             self.queue.push(hash);
             Ok(false)
         } else {
-            trace!("Message SHA256 {:X?} is a duplicate", &hash[..]);
+            trace!("Message hash {:X?} is a duplicate", hash);
             Ok(true)
         }
     }
 
     fn invalidate_if_exists(&mut self, input: &[u8]) {
-        let hash = self.hash(input);
+        let hash = self.hasher.hash(input);
         if let Some(old_val) = self.queue.iter_mut().find(|val| **val == hash) {
-            // zero out all bits in the old hash; we can't just remove the value from the
-            // queue
-            *old_val = Default::default();
+            // we can't just remove the value from the queue
+            *old_val = H::invalidated(*old_val);
         }
     }
 }
 
+/// XxHash64 deduplication queue
+pub type DeduplicationQueueXxHash64 = HashDeduplicationQueue<XxHash64Hasher>;
+
+/// SHA256 deduplication queue
+pub type DeduplicationQueueSha256 = HashDeduplicationQueue<Sha256Hasher>;
+
 /// Contains the circular queues of hashes of different consensus objects
 /// for deduplication purposes.
 pub struct DeduplicationQueues {
@@ -235,6 +260,8 @@ pub struct ConnectionStats {
     pub bytes_received:    AtomicU64,
     /// Number of bytes sent.
     pub bytes_sent:        AtomicU64,
+    /// Number of received messages that were dropped as unwanted.
+    pub unwanted_messages: AtomicU64,
 }
 
 impl ConnectionStats {
@@ -250,6 +277,7 @@ impl ConnectionStats {
             messages_received:  AtomicU64::new(0),
             bytes_received:     AtomicU64::new(0),
             bytes_sent:         AtomicU64::new(0),
+            unwanted_messages:  AtomicU64::new(0),
         }
     }
 
@@ -375,6 +403,9 @@ pub struct Connection {
     pub pending_messages:    MessageQueues,
     /// The wire protocol version for communicating on the connection.
     pub wire_version:        WireProtocolVersion,
+    /// Whether reading stopped at the read budget rather than because no more
+    /// data was available.
+    has_unread_data:         bool,
 }
 
 impl PartialEq for Connection {
@@ -410,6 +441,7 @@ impl Connection {
             handler,
             socket,
             is_initiator,
+            remote_peer.peer_type,
             handler.config.socket_read_size,
             handler.config.socket_write_size,
         );
@@ -433,6 +465,7 @@ impl Connection {
             // When we create the connection, we set the wire protocol version
             // to the current version, but this is overwritten in the handshake.
             wire_version: WIRE_PROTOCOL_CURRENT_VERSION,
+            has_unread_data: false,
         })
     }
 
@@ -461,11 +494,7 @@ impl Connection {
     #[inline]
     fn is_packet_duplicate(&self, packet: &mut NetworkPacket) -> anyhow::Result<bool> {
         use super::network::PacketDestination;
-        let packet_type = if let Some(tag) = packet.message.first().copied() {
-            PacketType::try_from(tag)?
-        } else {
-            bail!("Invalid message type.")
-        };
+        let (packet_type, _) = PacketType::read_from(&packet.message)?;
 
         if let PacketDestination::Direct(_) = packet.destination {
             return Ok(false);
@@ -494,21 +523,52 @@ impl Connection {
         Ok(is_duplicate)
     }
 
-    /// Keeps reading from the socket as long as there is data to be read
-    /// and the operation is not blocking.
+    /// Keeps reading from the socket as long as there is data to be read,
+    /// the operation is not blocking and the read budget isn't exhausted.
     /// The return value indicates if the connection is still open.
     #[inline]
     pub fn read_stream(&mut self, conn_stats: &[PeerStats]) -> anyhow::Result<bool> {
+        let mut budget = self.handler.config.socket_read_budget;
         loop {
-            match self.low_level.read_from_socket()? {
-                ReadResult::Complete(msg) => self.process_message(Arc::from(msg), conn_stats)?,
+            if budget == Some(0) {
+                self.has_unread_data = true;
+                return Ok(true);
+            }
+            let read_result = self.low_level.read_from_socket().map_err(|e| {
+                if e.is::<ZeroSizedMessage>() {
+                    self.handler.stats.zero_sized_messages.inc();
+                }
+                e
+            })?;
+            match read_result {
+                ReadResult::Complete(msg) => {
+                    if let Some(ref mut budget) = budget {
+                        *budget = budget.saturating_sub(msg.len());
+                    }
+                    self.process_message(Arc::from(msg), conn_stats)?
+                }
                 ReadResult::Incomplete => {}
-                ReadResult::WouldBlock => return Ok(true),
+                ReadResult::WouldBlock => {
+                    self.has_unread_data = false;
+                    return Ok(true);
+                }
                 ReadResult::Closed => return Ok(false),
             }
         }
     }
 
+    /// Check whether the connection may have data left to be read, because
+    /// reading stopped at the read budget.
+    #[inline]
+    pub fn has_unread_data(&self) -> bool { self.has_unread_data }
+
+    /// Record that a message from the peer was dropped because it is not
+    /// wanted, e.g., because of our role or the state of the connection.
+    pub(crate) fn register_unwanted_message(&self, reason: &str) {
+        self.stats.unwanted_messages.fetch_add(1, Ordering::Relaxed);
+        self.handler.stats.unwanted_messages.with_label_values(&[reason]).inc();
+    }
+
     #[inline]
     fn process_message(
         &mut self,
@@ -528,10 +588,30 @@ impl Connection {
         }
 
         let mut message = NetworkMessage::deserialize(&bytes)?;
+        if let Some(transit_time) = message.transit_time() {
+            self.handler.stats.message_transit_time.observe(transit_time as f64 / 1000.0);
+        }
+
+        let is_stale = self
+            .handler
+            .config
+            .max_broadcast_age
+            .map_or(false, |max_age| message.is_stale_broadcast(max_age));
 
         if let NetworkPayload::NetworkPacket(ref mut packet) = message.payload {
+            self.handler
+                .stats
+                .received_network_packets
+                .with_label_values(&[packet.destination.kind()])
+                .inc();
             // disregard packets when in bootstrapper mode
-            if self.handler.self_peer.peer_type == PeerType::Bootstrapper {
+            if !self.handler.self_peer.peer_type.accepts_packets() {
+                self.register_unwanted_message("packet to bootstrapper");
+                return Ok(());
+            }
+            // as well as broadcasts that were delayed for too long
+            if is_stale {
+                self.register_unwanted_message("stale broadcast");
                 return Ok(());
             }
             // deduplicate the incoming packet payload
@@ -556,8 +636,10 @@ impl Connection {
         self.remote_peer.external_port = peer_port;
         self.handler.stats.connected_peers.inc();
         self.handler.stats.total_peers.inc();
-        if self.remote_peer.peer_type == PeerType::Bootstrapper {
+        self.handler.stats.advance_node_state(NodeState::Ready);
+        if self.remote_peer.peer_type.is_bootstrap_source() {
             self.handler.update_last_bootstrap();
+            self.handler.register_bootstrapper_handshake(self.remote_peer.addr);
         }
         self.populate_remote_end_networks(self.remote_peer, nets);
         self.wire_version = wire_version;
@@ -574,31 +656,69 @@ impl Connection {
         self.pending_messages.enqueue(priority, message);
     }
 
+    /// Check whether more than `high_water_mark` bytes are waiting to be sent
+    /// to the connection, counting both the queued messages and the bytes
+    /// already handed to the low-level connection.
+    pub fn is_saturated(&self, high_water_mark: usize) -> bool {
+        let mut pending = self.low_level.pending_write_bytes();
+        for message in self.pending_messages.high.iter().chain(self.pending_messages.low.iter()) {
+            if pending > high_water_mark {
+                return true;
+            }
+            pending += message.len();
+        }
+        pending > high_water_mark
+    }
+
     /// Update the timestamp of when the connection was seen last.
     #[inline]
     pub fn update_last_seen(&self) {
-        if self.handler.peer_type() != PeerType::Bootstrapper {
+        if self.handler.peer_type().monitors_connections() {
             self.stats.last_seen.store(get_current_stamp(), Ordering::Relaxed);
         }
     }
 
+    /// Check whether the node keeps track of the given network. This is only
+    /// restricted for bootstrappers, which can be configured to track only
+    /// some networks in their buckets.
+    pub(crate) fn is_tracked_network(&self, network: NetworkId) -> bool {
+        !self.handler.peer_type().restricts_tracked_networks()
+            || self
+                .handler
+                .config
+                .tracked_networks
+                .as_ref()
+                .map_or(true, |tracked| tracked.contains(&network))
+    }
+
     /// Register connection's remote end networks.
     pub fn populate_remote_end_networks(&mut self, peer: RemotePeer, networks: &Networks) {
+        let networks = networks
+            .iter()
+            .copied()
+            .filter(|&net| self.is_tracked_network(net))
+            .collect::<Networks>();
         self.remote_end_networks.extend(networks.iter());
 
-        if self.remote_peer.peer_type != PeerType::Bootstrapper {
-            write_or_die!(self.handler.buckets()).insert_into_bucket(
+        if peer.external_port == 0 {
+            debug!("Not adding peer {} to the buckets; it doesn't accept connections", peer.addr);
+        } else if self.remote_peer.peer_type.is_kept_in_buckets() {
+            let inserted = write_or_die!(self.handler.buckets()).insert_into_bucket(
                 peer,
-                networks.to_owned(),
+                networks,
+                self.handler.config.max_peers_per_subnet,
                 &self.handler.stats.peer_bucket_size,
             );
+            if !inserted {
+                debug!("Not adding peer {} to the buckets; its subnet is full", peer.addr);
+            }
         }
     }
 
     /// Add a single network to the connection's remote end networks.
     pub fn add_remote_end_network(&mut self, network: NetworkId) -> anyhow::Result<()> {
         ensure!(
-            self.remote_end_networks.len() < MAX_PEER_NETWORKS,
+            self.remote_end_networks.len() < self.handler.config.max_peer_networks,
             "refusing to add any more networks"
         );
 
@@ -688,7 +808,7 @@ impl Connection {
             PeerType::Node => {
                 let nodes = conn_stats
                     .iter()
-                    .filter(|stat| stat.local_id != requestor)
+                    .filter(|stat| stat.local_id != requestor && stat.external_port != 0)
                     .map(|stat| P2PPeer {
                         id:        stat.self_id,
                         addr:      stat.external_address(),
@@ -719,27 +839,42 @@ impl Connection {
     }
 
     /// Processes a queue with pending messages, writing them to the socket.
+    /// Messages are only handed over to the socket as long as it keeps up, so
+    /// that while it is slow, any `High` priority messages enqueued in the
+    /// meantime are sent before the remaining `Normal` ones.
     #[inline]
     pub fn send_pending_messages(&mut self) -> anyhow::Result<()> {
-        while let Some(msg) = self.pending_messages.dequeue() {
-            trace!(
-                "Attempting to send {} to {}",
-                ByteSize(msg.len() as u64).to_string_as(true),
-                self
-            );
+        loop {
+            while !self.low_level.is_backlogged() {
+                if let Some(msg) = self.pending_messages.dequeue() {
+                    self.send_message(msg)?;
+                } else {
+                    return Ok(());
+                }
+            }
+            self.low_level.flush_socket()?;
+            if self.low_level.is_backlogged() {
+                return Ok(());
+            }
+        }
+    }
 
-            self.low_level.write_to_socket(msg.clone())?;
+    /// Writes a single message to the socket and registers it in the stats.
+    #[inline]
+    fn send_message(&mut self, msg: Arc<[u8]>) -> anyhow::Result<()> {
+        trace!("Attempting to send {} to {}", ByteSize(msg.len() as u64).to_string_as(true), self);
 
-            self.handler.connection_handler.total_sent.fetch_add(1, Ordering::Relaxed);
-            self.handler.stats.packets_sent.inc();
-            self.handler.stats.sent_bytes.inc_by(msg.len() as u64);
-            self.stats.messages_sent.fetch_add(1, Ordering::Relaxed);
-            self.stats.bytes_sent.fetch_add(msg.len() as u64, Ordering::Relaxed);
+        self.low_level.write_to_socket(msg.clone())?;
 
-            #[cfg(feature = "network_dump")]
-            {
-                self.send_to_dump(msg, false);
-            }
+        self.handler.connection_handler.total_sent.fetch_add(1, Ordering::Relaxed);
+        self.handler.stats.packets_sent.inc();
+        self.handler.stats.sent_bytes.inc_by(msg.len() as u64);
+        self.stats.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.stats.bytes_sent.fetch_add(msg.len() as u64, Ordering::Relaxed);
+
+        #[cfg(feature = "network_dump")]
+        {
+            self.send_to_dump(msg, false);
         }
 
         Ok(())
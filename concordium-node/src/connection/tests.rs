@@ -1,17 +1,41 @@
+use concordium_base::hashes::BlockHash;
 use itertools::Itertools;
+use prometheus::IntCounterVec;
 
 use crate::{
-    common::PeerType,
+    common::{get_current_stamp, p2p_peer::RemotePeerId, PeerType},
+    connection::{
+        DeduplicationQueue, HashDeduplicationQueue, MessageHasher, MessageQueues,
+        MessageSendingPriority, Sha256Hasher, XxHash64Hasher,
+    },
     consensus_ffi::helpers::PacketType,
-    network::NetworkId,
-    p2p::connectivity::send_broadcast_message,
+    lock_or_die, netmsg,
+    network::{NetworkId, NetworkMessage, NetworkPacket, NetworkRequest, PacketDestination},
+    p2p::{
+        connectivity::{
+            is_own_broadcast_allowed, packet_priority, send_broadcast_message, send_direct_message,
+            BroadcastLimiter, DeferredConnect, DeferredConnects,
+        },
+        P2PNode,
+    },
+    read_or_die,
     test_utils::{
-        await_handshakes, connect, dummy_regenesis_blocks, make_node_and_sync, next_available_port,
-        stop_node_delete_dirs,
+        await_cluster_handshakes, await_condition, await_handshakes, connect,
+        dummy_regenesis_blocks, get_test_config, make_cluster, make_node_and_sync,
+        make_node_and_sync_with_config, next_available_port, stop_node_delete_dirs,
     },
+    write_or_die,
 };
 
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    io::Write,
+    net::TcpStream,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 const NID: u16 = 100;
 const NODE_COUNT: usize = 10;
@@ -71,3 +95,901 @@ fn basic_connectivity() {
         stop_node_delete_dirs(dp, node);
     }
 }
+
+#[test]
+fn zero_sized_message_drops_connection() {
+    let (node, dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+
+    let has_connection = || {
+        !lock_or_die!(node.conn_candidates()).is_empty()
+            || !read_or_die!(node.connections()).is_empty()
+    };
+
+    let mut stream = TcpStream::connect(node.self_peer.addr).unwrap();
+    await_condition(Duration::from_secs(5), "the connection", has_connection);
+
+    // announce a message of length 0 in place of the first handshake message
+    stream.write_all(&0u32.to_be_bytes()).unwrap();
+    await_condition(Duration::from_secs(5), "the connection to close", || !has_connection());
+    assert_eq!(node.stats.zero_sized_messages.get(), 1);
+
+    stop_node_delete_dirs(dp, node);
+}
+
+#[test]
+fn peer_stats_report_network_membership() {
+    let (node_1, dp_1) = make_node_and_sync(
+        next_available_port(),
+        vec![100, 200],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (node_2, dp_2) = make_node_and_sync(
+        next_available_port(),
+        vec![200, 300],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+
+    connect(&node_1, &node_2);
+    await_handshakes(&node_1);
+    await_handshakes(&node_2);
+
+    let networks_of_peer = |node: &P2PNode| {
+        let stats = node.get_peer_stats(None);
+        assert_eq!(stats.len(), 1);
+        let mut networks = stats[0].networks.iter().map(|net| net.id).collect::<Vec<_>>();
+        networks.sort_unstable();
+        networks
+    };
+    assert_eq!(networks_of_peer(&node_1), vec![200, 300]);
+    assert_eq!(networks_of_peer(&node_2), vec![100, 200]);
+
+    stop_node_delete_dirs(dp_1, node_1);
+    stop_node_delete_dirs(dp_2, node_2);
+}
+
+#[test]
+fn cluster_completes_all_handshakes() {
+    let cluster = make_cluster(3, vec![NID]).unwrap();
+    await_cluster_handshakes(&cluster);
+
+    for (node, _) in &cluster {
+        assert_eq!(node.get_peer_stats(None).len(), cluster.len() - 1);
+    }
+
+    for (node, dp) in cluster {
+        stop_node_delete_dirs(dp, node);
+    }
+}
+
+#[test]
+fn peers_per_subnet_are_limited() {
+    const MAX_PEERS_PER_SUBNET: usize = 2;
+
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.max_peers_per_subnet = Some(MAX_PEERS_PER_SUBNET as u16);
+    let (node, dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+
+    // all the peers are in the same subnet, 127.0.0.0/24
+    let mut peers = Vec::with_capacity(4);
+    for _ in 0..4 {
+        let peer = make_node_and_sync(
+            next_available_port(),
+            vec![NID],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .unwrap();
+        connect(&node, &peer.0);
+        peers.push(peer);
+    }
+    await_handshakes(&node);
+    await_condition(Duration::from_secs(10), "the peers to connect", || {
+        node.get_peer_stats(None).len() >= MAX_PEERS_PER_SUBNET
+    });
+    // give any further connection attempts the time to conclude
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(node.get_peer_stats(None).len(), MAX_PEERS_PER_SUBNET);
+    assert_eq!(read_or_die!(node.buckets()).buckets[0].len(), MAX_PEERS_PER_SUBNET);
+
+    stop_node_delete_dirs(dp, node);
+    for (peer, dp) in peers {
+        stop_node_delete_dirs(dp, peer);
+    }
+}
+
+#[test]
+fn incoming_peers_per_subnet_are_limited() {
+    const MAX_PEERS_PER_SUBNET: usize = 2;
+
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.max_peers_per_subnet = Some(MAX_PEERS_PER_SUBNET as u16);
+    let (node, dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+
+    // all the peers are in the same subnet, 127.0.0.0/24
+    let mut peers = Vec::with_capacity(4);
+    for _ in 0..4 {
+        let peer = make_node_and_sync(
+            next_available_port(),
+            vec![NID],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .unwrap();
+        connect(&peer.0, &node);
+        peers.push(peer);
+    }
+    await_condition(Duration::from_secs(10), "the peers to connect", || {
+        node.get_peer_stats(None).len() >= MAX_PEERS_PER_SUBNET
+    });
+    // give any further connection attempts the time to conclude
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(node.get_peer_stats(None).len(), MAX_PEERS_PER_SUBNET);
+    assert_eq!(read_or_die!(node.buckets()).buckets[0].len(), MAX_PEERS_PER_SUBNET);
+
+    stop_node_delete_dirs(dp, node);
+    for (peer, dp) in peers {
+        stop_node_delete_dirs(dp, peer);
+    }
+}
+
+#[test]
+fn broadcast_fan_out_is_limited() {
+    const MAX_PEERS: u16 = 2;
+
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.broadcast_max_peers = Some(MAX_PEERS);
+    let (source, source_dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+
+    let mut peers = Vec::with_capacity(4);
+    for _ in 0..4 {
+        let peer = make_node_and_sync(
+            next_available_port(),
+            vec![NID],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .unwrap();
+        connect(&source, &peer.0);
+        peers.push(peer);
+    }
+    for (peer, _) in &peers {
+        await_handshakes(peer);
+    }
+    await_handshakes(&source);
+    await_condition(Duration::from_secs(5), "the peers", || {
+        source.get_peer_stats(None).len() >= peers.len()
+    });
+
+    let recipients = send_broadcast_message(
+        &source,
+        vec![],
+        NetworkId::from(NID),
+        Arc::from(&[PacketType::Block as u8][..]),
+    );
+    assert_eq!(recipients, MAX_PEERS as usize);
+
+    stop_node_delete_dirs(source_dp, source);
+    for (peer, dp) in peers {
+        stop_node_delete_dirs(dp, peer);
+    }
+}
+
+#[test]
+fn handshake_requires_common_genesis() {
+    let other_genesis = vec![BlockHash::new([9u8; 32])];
+    let (node, dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (other_chain, other_chain_dp) =
+        make_node_and_sync(next_available_port(), vec![NID], PeerType::Node, other_genesis)
+            .unwrap();
+    let (same_chain, same_chain_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+
+    // a peer with the same genesis blocks is accepted
+    connect(&node, &same_chain);
+    await_handshakes(&node);
+    await_handshakes(&same_chain);
+    let peers = node.get_peer_stats(None);
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].self_id, same_chain.id());
+
+    // a peer on a different chain is rejected during the handshake; this is done
+    // last since the rejection soft-bans the (shared) IP of the test nodes
+    connect(&node, &other_chain);
+    let rejections =
+        || node.stats.wrong_genesis_peers.get() + other_chain.stats.wrong_genesis_peers.get();
+    await_condition(Duration::from_secs(5), "the handshake", || rejections() > 0);
+    assert!(node.get_peer_stats(None).iter().all(|peer| peer.self_id != other_chain.id()));
+
+    stop_node_delete_dirs(dp, node);
+    stop_node_delete_dirs(other_chain_dp, other_chain);
+    stop_node_delete_dirs(same_chain_dp, same_chain);
+}
+
+#[test]
+fn bootstrapper_drops_packets_as_unwanted() {
+    let (bootstrapper, bootstrapper_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Bootstrapper,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (node, dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+
+    connect(&node, &bootstrapper);
+    await_handshakes(&node);
+    await_handshakes(&bootstrapper);
+    let target = node.get_peer_stats(None)[0].local_id;
+
+    let sent = send_direct_message(
+        &node,
+        target,
+        NetworkId::from(NID),
+        Arc::from(&[PacketType::Block as u8][..]),
+    );
+    assert_eq!(sent, 1);
+
+    let unwanted =
+        bootstrapper.stats.unwanted_messages.with_label_values(&["packet to bootstrapper"]);
+    await_condition(Duration::from_secs(5), "the packet", || unwanted.get() > 0);
+    let peers = bootstrapper.get_peer_stats(None);
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].unwanted_messages, 1);
+
+    stop_node_delete_dirs(dp, node);
+    stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+}
+
+#[test]
+fn close_causes_are_recorded() {
+    let (node, dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    assert!(node.recent_close_causes().is_empty());
+
+    let mut stream = TcpStream::connect(node.self_peer.addr).unwrap();
+    let local_addr = stream.local_addr().unwrap();
+    await_condition(Duration::from_secs(5), "the connection", || {
+        !lock_or_die!(node.conn_candidates()).is_empty()
+    });
+
+    // the cause is kept after the connection is gone
+    stream.write_all(&0u32.to_be_bytes()).unwrap();
+    await_condition(Duration::from_secs(5), "the connection to close", || {
+        lock_or_die!(node.conn_candidates()).is_empty()
+    });
+    let causes = node.recent_close_causes();
+    assert_eq!(causes.len(), 1);
+    assert_eq!(causes[0].addr, local_addr);
+    assert_eq!(causes[0].peer_id, None);
+    assert_eq!(causes[0].error, "The peer sent a zero-sized message");
+
+    stop_node_delete_dirs(dp, node);
+}
+
+#[test]
+fn message_hashes_are_stable() {
+    assert_eq!(XxHash64Hasher::with_seed(0).hash(b""), 0xef46_db37_51d8_e999);
+    let seeded = XxHash64Hasher::default();
+    assert_eq!(seeded.hash(b"a message"), seeded.hash(b"a message"));
+
+    let sha256 = Sha256Hasher.hash(b"abc");
+    assert_eq!(
+        hex::encode(sha256),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(Sha256Hasher.hash(b"abc"), sha256);
+}
+
+#[test]
+fn deduplication_queue_detects_duplicates() {
+    fn check<H: MessageHasher>(mut queue: HashDeduplicationQueue<H>) {
+        assert!(!queue.check_and_insert(b"first").unwrap());
+        assert!(queue.check_and_insert(b"first").unwrap());
+        assert!(!queue.check_and_insert(b"second").unwrap());
+        queue.invalidate_if_exists(b"first");
+        assert!(!queue.check_and_insert(b"first").unwrap());
+    }
+    check(HashDeduplicationQueue::with_hasher(XxHash64Hasher::with_seed(0), 4));
+    check(HashDeduplicationQueue::<Sha256Hasher>::new(4));
+}
+
+#[test]
+fn failed_encryption_is_reported() {
+    use crate::connection::low_level::{
+        encrypt_frame, NOISE_MAX_MESSAGE_LEN, NOISE_MAX_PAYLOAD_LEN,
+    };
+
+    let input = vec![1u8; 2 * NOISE_MAX_PAYLOAD_LEN + 10];
+    let mut noise_buffer = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+
+    // a session that fails to encrypt the second chunk; no further chunks are
+    // encrypted
+    let mut chunks = 0;
+    let result = encrypt_frame(&input, &mut noise_buffer, &mut VecDeque::new(), |_| {
+        chunks += 1;
+        anyhow::ensure!(chunks < 2, "encryption failed");
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(chunks, 2);
+
+    // a session that succeeds appends the length followed by all the chunks
+    // after the bytes of an earlier frame that are still waiting to be sent
+    let mut output = VecDeque::from(vec![7u8; 3]);
+    encrypt_frame(&input, &mut noise_buffer, &mut output, |_| Ok(())).unwrap();
+    let output = Vec::from(output);
+    assert_eq!(output[..3], [7u8; 3]);
+    let frame = &output[3..];
+    let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+    assert_eq!(
+        len,
+        2 * NOISE_MAX_MESSAGE_LEN + 10 + (NOISE_MAX_MESSAGE_LEN - NOISE_MAX_PAYLOAD_LEN)
+    );
+    assert_eq!(frame.len(), 4 + len);
+}
+
+#[test]
+fn frame_lengths_are_big_endian() {
+    use crate::connection::low_level::{
+        decode_plaintext_frames, encrypt_frame, NOISE_MAX_MESSAGE_LEN,
+    };
+
+    let input = [0xab; 300];
+    let mut noise_buffer = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+
+    // the length of the frame, i.e. the input and the authentication tag,
+    // precedes the noise message
+    let mut frame = VecDeque::new();
+    encrypt_frame(&input, &mut noise_buffer, &mut frame, |_| Ok(())).unwrap();
+    let frame = Vec::from(frame);
+    assert_eq!(frame[..4], [0x00, 0x00, 0x01, 0x3c]);
+    assert_eq!(frame[4..304], input[..]);
+    assert_eq!(frame[304..], [0u8; 16]);
+
+    // the same length prefix is expected in captured plaintext frames
+    let mut stream = vec![0x00, 0x00, 0x01, 0x3c];
+    stream.extend_from_slice(&[0u8; 0x13c]);
+    let err = decode_plaintext_frames(&stream).unwrap_err();
+    assert!(err.to_string().contains("invalid message in frame 0"));
+    assert!(decode_plaintext_frames(&stream[..stream.len() - 1])
+        .unwrap_err()
+        .to_string()
+        .contains("expected 316 bytes"));
+}
+
+#[test]
+fn captured_frames_are_replayed() {
+    use crate::{
+        connection::low_level::{decode_plaintext_frames, ZeroSizedMessage},
+        network::NetworkResponse,
+    };
+
+    let messages = vec![
+        netmsg!(NetworkRequest, NetworkRequest::Ping),
+        netmsg!(NetworkPacket, NetworkPacket {
+            destination: PacketDestination::Broadcast(vec![]),
+            network_id:  NetworkId::from(NID),
+            message:     vec![PacketType::Block as u8, 1, 2, 3],
+        }),
+        netmsg!(NetworkResponse, NetworkResponse::Pong),
+    ];
+    let mut stream = Vec::new();
+    for message in &messages {
+        let mut bytes = Vec::new();
+        message.serialize(&mut bytes).unwrap();
+        stream.write_all(&(bytes.len() as u32).to_be_bytes()).unwrap();
+        stream.write_all(&bytes).unwrap();
+    }
+
+    let replayed = decode_plaintext_frames(&stream).unwrap();
+    assert_eq!(replayed.len(), messages.len());
+    for (replayed, message) in replayed.iter().zip(messages.iter()) {
+        assert_eq!(replayed.payload, message.payload);
+    }
+
+    // an incomplete frame at the end of the stream
+    assert!(decode_plaintext_frames(&stream[..stream.len() - 1]).is_err());
+    assert!(decode_plaintext_frames(&stream[..2]).is_err());
+    // a zero-sized frame
+    let err = decode_plaintext_frames(&[0, 0, 0, 0]).unwrap_err();
+    assert!(err.is::<ZeroSizedMessage>());
+    // a frame that doesn't contain a valid message
+    assert!(decode_plaintext_frames(&[0, 0, 0, 3, 1, 2, 3]).is_err());
+}
+
+#[test]
+fn urgent_packets_have_high_priority() {
+    let packet = |destination, packet_type: PacketType| NetworkPacket {
+        destination,
+        network_id: NetworkId::from(NID),
+        message: vec![packet_type as u8],
+    };
+    let broadcast = || PacketDestination::Broadcast(vec![]);
+    let direct = || PacketDestination::Direct(RemotePeerId::from(1usize));
+
+    assert_eq!(packet_priority(&packet(direct(), PacketType::Block)), MessageSendingPriority::High);
+    for packet_type in &[PacketType::FinalizationMessage, PacketType::FinalizationRecord] {
+        assert_eq!(
+            packet_priority(&packet(broadcast(), *packet_type)),
+            MessageSendingPriority::High
+        );
+    }
+    for packet_type in &[PacketType::Block, PacketType::Transaction] {
+        assert_eq!(
+            packet_priority(&packet(broadcast(), *packet_type)),
+            MessageSendingPriority::Normal
+        );
+    }
+}
+
+#[test]
+fn message_queues_keep_order_within_priority() {
+    let mut queues = MessageQueues::new(4, 4);
+    for msg in &[b"low 1", b"low 2"] {
+        queues.enqueue(MessageSendingPriority::Normal, Arc::from(&msg[..]));
+    }
+    for msg in &[b"high 1", b"high 2"] {
+        queues.enqueue(MessageSendingPriority::High, Arc::from(&msg[..]));
+    }
+    // a high priority message enqueued after some have already been sent still
+    // goes before all the waiting normal ones
+    assert_eq!(&*queues.dequeue().unwrap(), b"high 1");
+    queues.enqueue(MessageSendingPriority::High, Arc::from(&b"high 3"[..]));
+
+    let rest = std::iter::from_fn(|| queues.dequeue()).collect::<Vec<_>>();
+    let rest = rest.iter().map(|msg| &msg[..]).collect::<Vec<_>>();
+    assert_eq!(rest, vec![&b"high 2"[..], b"high 3", b"low 1", b"low 2"]);
+}
+
+#[test]
+fn high_priority_messages_overtake_a_backlog() {
+    const FILLER_SIZE: usize = 1 << 20;
+
+    let (node_1, dp_1) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (node_2, dp_2) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    connect(&node_1, &node_2);
+    await_handshakes(&node_1);
+    await_handshakes(&node_2);
+    let received = || node_2.get_peer_stats(None)[0].msgs_received;
+    let base = received();
+
+    let serialize = |message: NetworkMessage| {
+        let mut bytes = Vec::new();
+        message.serialize(&mut bytes).unwrap();
+        Arc::<[u8]>::from(bytes)
+    };
+    // identical blocks, so that the peer only passes the first one on
+    let mut block = vec![PacketType::Block as u8, 0, 0, 0, 0];
+    block.resize(FILLER_SIZE, 0);
+    let filler = serialize(netmsg!(NetworkPacket, NetworkPacket {
+        destination: PacketDestination::Broadcast(vec![]),
+        network_id:  NetworkId::from(NID),
+        message:     block,
+    }));
+
+    // keep the peer from reading, which it does with the candidates locked, and
+    // queue messages until the connection's socket can't keep up
+    let candidates = lock_or_die!(node_2.conn_candidates());
+    let mut fillers = 0;
+    await_condition(Duration::from_secs(10), "the socket to fill up", || {
+        let mut connections = write_or_die!(node_1.connections());
+        let conn = connections.values_mut().next().unwrap();
+        if conn.low_level.is_backlogged() && !conn.pending_messages.low.is_empty() {
+            return true;
+        }
+        for _ in 0..4 {
+            conn.async_send(Arc::clone(&filler), MessageSendingPriority::Normal);
+        }
+        fillers += 4;
+        false
+    });
+
+    // joining and then leaving a network in this order would leave the peer
+    // outside it; the other way around it ends up in it
+    let net = NetworkId::from(200);
+    {
+        let mut connections = write_or_die!(node_1.connections());
+        let conn = connections.values_mut().next().unwrap();
+        conn.async_send(
+            serialize(netmsg!(NetworkRequest, NetworkRequest::JoinNetwork(net))),
+            MessageSendingPriority::Normal,
+        );
+        conn.async_send(
+            serialize(netmsg!(NetworkRequest, NetworkRequest::LeaveNetwork(net))),
+            MessageSendingPriority::High,
+        );
+    }
+    drop(candidates);
+
+    await_condition(Duration::from_secs(30), "the messages", || received() >= base + fillers + 2);
+    // the high priority message was sent ahead of the queued normal ones
+    assert!(node_2.get_peer_stats(None)[0].networks.contains(&net));
+
+    stop_node_delete_dirs(dp_1, node_1);
+    stop_node_delete_dirs(dp_2, node_2);
+}
+
+#[test]
+fn broadcast_limiter_allows_rate() {
+    let mut limiter = BroadcastLimiter::new(2);
+    let (net_1, net_2) = (NetworkId::from(100), NetworkId::from(200));
+    let start = Instant::now();
+
+    // a burst of up to the rate is allowed in each network
+    assert!(limiter.allow(net_1, start));
+    assert!(limiter.allow(net_1, start));
+    assert!(!limiter.allow(net_1, start));
+    assert!(limiter.allow(net_2, start));
+    // and the tokens are replenished over time
+    assert!(!limiter.allow(net_1, start + Duration::from_millis(250)));
+    assert!(limiter.allow(net_1, start + Duration::from_millis(500)));
+    assert!(limiter.allow(net_1, start + Duration::from_secs(10)));
+    assert!(limiter.allow(net_1, start + Duration::from_secs(10)));
+    assert!(!limiter.allow(net_1, start + Duration::from_secs(10)));
+}
+
+#[test]
+fn deferred_connects_are_bounded() {
+    let attempt = |port| DeferredConnect {
+        peer_type:         PeerType::Node,
+        addr:              ([127, 0, 0, 1], port).into(),
+        peer_id:           None,
+        respect_max_peers: true,
+    };
+    let mut deferred = DeferredConnects::new(2);
+    let start = Instant::now();
+
+    // an address is only queued once
+    assert!(deferred.push(attempt(8000), start));
+    assert!(!deferred.push(attempt(8000), start));
+    // and the queue is capped
+    assert!(deferred.push(attempt(8001), start + Duration::from_secs(10)));
+    assert!(!deferred.push(attempt(8002), start + Duration::from_secs(10)));
+    assert_eq!(deferred.len(), 2);
+
+    // old attempts are dropped, making room for new ones
+    deferred.expire(start + Duration::from_secs(30), Duration::from_secs(25));
+    assert_eq!(deferred.len(), 1);
+    assert!(deferred.push(attempt(8000), start + Duration::from_secs(30)));
+    assert_eq!(deferred.pop().map(|attempt| attempt.addr.port()), Some(8001));
+    assert_eq!(deferred.pop().map(|attempt| attempt.addr.port()), Some(8000));
+    assert!(deferred.is_empty());
+}
+
+#[test]
+fn broadcasts_are_throttled() {
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.broadcast_rate_limit = Some(2);
+    let (node, dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+
+    let allowed = (0..5)
+        .filter(|_| is_own_broadcast_allowed(&node, NetworkId::from(NID), PacketType::Block))
+        .count();
+    assert_eq!(allowed, 2);
+    assert_eq!(node.stats.broadcasts_throttled.get(), 3);
+    // finalization messages and records are not limited
+    assert!(is_own_broadcast_allowed(&node, NetworkId::from(NID), PacketType::FinalizationMessage));
+    assert!(is_own_broadcast_allowed(&node, NetworkId::from(NID), PacketType::FinalizationRecord));
+    // and neither are relayed broadcasts and direct messages
+    send_broadcast_message(
+        &node,
+        vec![],
+        NetworkId::from(NID),
+        Arc::from(&[PacketType::Block as u8][..]),
+    );
+    send_direct_message(
+        &node,
+        RemotePeerId::from(1usize),
+        NetworkId::from(NID),
+        Arc::from(&[PacketType::Block as u8][..]),
+    );
+    assert_eq!(node.stats.broadcasts_throttled.get(), 3);
+
+    stop_node_delete_dirs(dp, node);
+}
+
+/// Queue messages on the connection of `node` to `peer` until it is
+/// saturated. The caller has to lock the node's connection candidates to keep
+/// the poll loop from sending the messages.
+fn saturate_connection(node: &P2PNode, peer: &P2PNode, high_water_mark: usize) {
+    let peer_id = node
+        .get_peer_stats(None)
+        .into_iter()
+        .find(|stats| stats.self_id == peer.id())
+        .unwrap()
+        .local_id;
+    let token = node.find_conn_token_by_id(peer_id).unwrap();
+    let mut conn = write_or_die!(node.connections()).remove(&token).unwrap();
+    assert!(!conn.is_saturated(high_water_mark));
+    let mut ping = Vec::new();
+    netmsg!(NetworkRequest, NetworkRequest::Ping).serialize(&mut ping).unwrap();
+    let ping = Arc::<[u8]>::from(ping);
+    while conn.pending_messages.low.len() * ping.len() <= high_water_mark {
+        conn.async_send(Arc::clone(&ping), MessageSendingPriority::Normal);
+    }
+    assert!(conn.is_saturated(high_water_mark));
+    write_or_die!(node.connections()).insert(token, conn);
+}
+
+#[test]
+fn saturated_peers_are_skipped() {
+    const HIGH_WATER_MARK: usize = 1024;
+
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.broadcast_high_water_mark = Some(HIGH_WATER_MARK);
+    let (source, source_dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+    let (healthy, healthy_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (saturated, saturated_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    connect(&source, &healthy);
+    connect(&source, &saturated);
+    await_handshakes(&healthy);
+    await_handshakes(&saturated);
+    await_condition(Duration::from_secs(5), "the peers", || source.get_peer_stats(None).len() >= 2);
+
+    let broadcast = || {
+        send_broadcast_message(
+            &source,
+            vec![],
+            NetworkId::from(NID),
+            Arc::from(&[PacketType::Block as u8][..]),
+        )
+    };
+    assert_eq!(broadcast(), 2);
+    assert_eq!(source.stats.broadcast_deliveries_skipped.get(), 0);
+
+    // keep the poll loop from draining the queues, which it does with the
+    // candidates locked, until the broadcast is done
+    let candidates = lock_or_die!(source.conn_candidates());
+    saturate_connection(&source, &saturated, HIGH_WATER_MARK);
+
+    assert_eq!(broadcast(), 1);
+    assert_eq!(source.stats.broadcast_deliveries_skipped.get(), 1);
+    drop(candidates);
+
+    stop_node_delete_dirs(source_dp, source);
+    stop_node_delete_dirs(healthy_dp, healthy);
+    stop_node_delete_dirs(saturated_dp, saturated);
+}
+
+#[test]
+fn limited_fan_out_skips_saturated_peers() {
+    const MAX_PEERS: u16 = 2;
+    const HIGH_WATER_MARK: usize = 1024;
+    const BROADCASTS: usize = 10;
+
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.broadcast_max_peers = Some(MAX_PEERS);
+    config.connection.broadcast_high_water_mark = Some(HIGH_WATER_MARK);
+    let (source, source_dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+    let mut peers = Vec::with_capacity(3);
+    for _ in 0..3 {
+        let peer = make_node_and_sync(
+            next_available_port(),
+            vec![NID],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .unwrap();
+        connect(&source, &peer.0);
+        peers.push(peer);
+    }
+    for (peer, _) in &peers {
+        await_handshakes(peer);
+    }
+    await_condition(Duration::from_secs(5), "the peers", || {
+        source.get_peer_stats(None).len() >= peers.len()
+    });
+
+    let candidates = lock_or_die!(source.conn_candidates());
+    saturate_connection(&source, &peers[0].0, HIGH_WATER_MARK);
+
+    // the saturated peer is never selected, so that the broadcasts still reach
+    // the full fan-out
+    for _ in 0..BROADCASTS {
+        let recipients = send_broadcast_message(
+            &source,
+            vec![],
+            NetworkId::from(NID),
+            Arc::from(&[PacketType::Block as u8][..]),
+        );
+        assert_eq!(recipients, MAX_PEERS as usize);
+    }
+    assert_eq!(source.stats.broadcast_deliveries_skipped.get(), BROADCASTS as u64);
+    drop(candidates);
+
+    stop_node_delete_dirs(source_dp, source);
+    for (peer, dp) in peers {
+        stop_node_delete_dirs(dp, peer);
+    }
+}
+
+#[test]
+fn packets_are_counted_by_destination() {
+    let (node_1, dp_1) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (node_2, dp_2) = make_node_and_sync(
+        next_available_port(),
+        vec![NID],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    connect(&node_1, &node_2);
+    await_handshakes(&node_1);
+    await_handshakes(&node_2);
+
+    let count =
+        |counters: &IntCounterVec, destination| counters.with_label_values(&[destination]).get();
+    let await_count = |node: &P2PNode, destination, expected| {
+        await_condition(Duration::from_secs(5), "the packet", || {
+            count(&node.stats.received_network_packets, destination) >= expected
+        });
+    };
+
+    let target = node_1.get_peer_stats(None)[0].local_id;
+    let msg = Arc::<[u8]>::from(&[PacketType::Block as u8][..]);
+    assert_eq!(send_direct_message(&node_1, target, NetworkId::from(NID), msg.clone()), 1);
+    await_count(&node_2, "direct", 1);
+    assert_eq!(count(&node_1.stats.sent_network_packets, "direct"), 1);
+    assert_eq!(count(&node_1.stats.sent_network_packets, "broadcast"), 0);
+    assert_eq!(count(&node_2.stats.received_network_packets, "broadcast"), 0);
+
+    assert_eq!(send_broadcast_message(&node_1, vec![], NetworkId::from(NID), msg), 1);
+    await_count(&node_2, "broadcast", 1);
+    assert_eq!(count(&node_1.stats.sent_network_packets, "direct"), 1);
+    assert_eq!(count(&node_1.stats.sent_network_packets, "broadcast"), 1);
+    assert_eq!(count(&node_2.stats.received_network_packets, "direct"), 1);
+
+    stop_node_delete_dirs(dp_1, node_1);
+    stop_node_delete_dirs(dp_2, node_2);
+}
+
+#[test]
+fn read_budget_doesnt_lose_messages() {
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.socket_read_budget = Some(1);
+    let (receiver, receiver_dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+    let mut senders = Vec::new();
+    for _ in 0..2 {
+        let (sender, sender_dp) = make_node_and_sync(
+            next_available_port(),
+            vec![NID],
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )
+        .unwrap();
+        connect(&sender, &receiver);
+        await_handshakes(&sender);
+        senders.push((sender, sender_dp));
+    }
+    await_handshakes(&receiver);
+
+    // each complete message exhausts the budget, so the bursts can only be read
+    // in full if exhausted connections are resumed without new events
+    let burst = 20;
+    let msg = Arc::<[u8]>::from(&[PacketType::Block as u8][..]);
+    for (sender, _) in &senders {
+        let target = sender.get_peer_stats(None)[0].local_id;
+        for _ in 0..burst {
+            assert_eq!(send_direct_message(sender, target, NetworkId::from(NID), msg.clone()), 1);
+        }
+    }
+
+    let received = &receiver.stats.received_network_packets;
+    await_condition(Duration::from_secs(5), "the packets", || {
+        received.with_label_values(&["direct"]).get() >= 2 * burst
+    });
+
+    for (sender, sender_dp) in senders {
+        stop_node_delete_dirs(sender_dp, sender);
+    }
+    stop_node_delete_dirs(receiver_dp, receiver);
+}
+
+#[test]
+fn handshake_limits_peer_networks() {
+    let mut config = get_test_config(next_available_port(), vec![NID]);
+    config.connection.max_peer_networks = 2;
+    let (node, dp) =
+        make_node_and_sync_with_config(config, PeerType::Node, dummy_regenesis_blocks()).unwrap();
+    let (at_cap, at_cap_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID, NID + 1],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+    let (over_cap, over_cap_dp) = make_node_and_sync(
+        next_available_port(),
+        vec![NID, NID + 1, NID + 2],
+        PeerType::Node,
+        dummy_regenesis_blocks(),
+    )
+    .unwrap();
+
+    connect(&at_cap, &node);
+    await_handshakes(&node);
+    await_handshakes(&at_cap);
+    assert_eq!(node.get_peer_stats(None)[0].self_id, at_cap.id());
+
+    // the rejection soft-bans the (shared) IP of the test nodes, so this is last
+    connect(&over_cap, &node);
+    await_condition(Duration::from_secs(5), "the rejection", || {
+        node.stats.soft_banned_peers_total.get() > 0
+    });
+    assert!(node.get_peer_stats(None).iter().all(|peer| peer.self_id != over_cap.id()));
+
+    stop_node_delete_dirs(dp, node);
+    stop_node_delete_dirs(at_cap_dp, at_cap);
+    stop_node_delete_dirs(over_cap_dp, over_cap);
+}
@@ -1,12 +1,16 @@
-use crate::consensus_ffi::{
-    ffi::{
-        consensus_runner, get_consensus_ptr, startBaker, stopBaker, stopConsensus,
-        StartConsensusConfig,
+use crate::{
+    consensus_ffi::{
+        ffi::{
+            consensus_runner, get_consensus_ptr, startBaker, stopBaker, stopConsensus,
+            StartConsensusConfig,
+        },
+        helpers::{QueueReceiver, QueueSyncSender, RelayOrStopSenderHelper},
+        messaging::ConsensusMessage,
     },
-    helpers::{QueueReceiver, QueueSyncSender, RelayOrStopSenderHelper},
-    messaging::ConsensusMessage,
+    stats_export_service::time_consensus_call,
 };
 use concordium_base::hashes::BlockHash;
+use prometheus::HistogramVec;
 use std::{
     convert::TryFrom,
     path::Path,
@@ -195,6 +199,9 @@ pub struct ConsensusContainer {
     pub consensus:          Arc<AtomicPtr<consensus_runner>>,
     pub genesis:            Arc<[u8]>,
     pub consensus_type:     ConsensusType,
+    /// Records the duration of the calls into consensus, labelled by the
+    /// operation.
+    call_duration:          Option<HistogramVec>,
 }
 
 impl ConsensusContainer {
@@ -212,6 +219,7 @@ impl ConsensusContainer {
             ConsensusType::Passive
         };
         let genesis_data = start_config.genesis_data.clone();
+        let call_duration = start_config.call_duration.clone();
 
         match get_consensus_ptr(&runtime_parameters, start_config, private_data, appdata_dir) {
             Ok(consensus_ptr) => Ok(Self {
@@ -220,17 +228,29 @@ impl ConsensusContainer {
                 consensus: Arc::new(AtomicPtr::new(consensus_ptr)),
                 genesis: Arc::from(genesis_data),
                 consensus_type,
+                call_duration,
             }),
             Err(e) => Err(e),
         }
     }
 
+    /// Make a call into consensus, recording its duration under the given
+    /// operation.
+    pub(crate) fn timed<T>(&self, operation: &str, call: impl FnOnce() -> T) -> T {
+        match self.call_duration {
+            Some(ref call_duration) => time_consensus_call(call_duration, operation, call),
+            None => call(),
+        }
+    }
+
     pub fn stop(&self) {
         self.stop_baker();
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        unsafe {
-            stopConsensus(consensus);
-        }
+        self.timed("stop_consensus", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            unsafe {
+                stopConsensus(consensus);
+            }
+        });
         CALLBACK_QUEUE.clear();
     }
 
@@ -241,10 +261,12 @@ impl ConsensusContainer {
 
         info!("Commencing baking");
 
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        unsafe {
-            startBaker(consensus);
-        }
+        self.timed("start_baker", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            unsafe {
+                startBaker(consensus);
+            }
+        });
         self.is_baking.store(true, Ordering::SeqCst);
 
         true
@@ -257,10 +279,12 @@ impl ConsensusContainer {
 
         info!("Stopping baking");
 
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        unsafe {
-            stopBaker(consensus);
-        }
+        self.timed("stop_baker", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            unsafe {
+                stopBaker(consensus);
+            }
+        });
         self.is_baking.store(false, Ordering::SeqCst);
 
         true
@@ -303,3 +327,44 @@ impl Regenesis {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::HistogramOpts;
+
+    #[cfg(unix)]
+    #[test]
+    fn container_calls_are_timed() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let call_duration =
+            HistogramVec::new(HistogramOpts::new("consensus_call_duration_seconds", "help"), &[
+                "operation",
+            ])
+            .unwrap();
+        // no consensus runner; the call below fails before reaching it
+        let container = ConsensusContainer {
+            runtime_parameters: ConsensusRuntimeParameters {
+                max_block_size:             0,
+                block_construction_timeout: 0,
+                insertions_before_purging:  0,
+                transaction_keep_alive:     0,
+                transactions_purging_delay: 0,
+                accounts_cache_size:        0,
+                modules_cache_size:         0,
+            },
+            is_baking:          Arc::new(AtomicBool::new(false)),
+            consensus:          Arc::new(AtomicPtr::new(std::ptr::null_mut())),
+            genesis:            Arc::from(&[][..]),
+            consensus_type:     ConsensusType::Passive,
+            call_duration:      Some(call_duration.clone()),
+        };
+
+        // a path that can't be passed to consensus
+        let path = Path::new(OsStr::from_bytes(b"\xff"));
+        assert!(container.import_blocks(path).is_err());
+        assert_eq!(call_duration.with_label_values(&["import_blocks"]).get_sample_count(), 1);
+        assert_eq!(call_duration.with_label_values(&["receive_block"]).get_sample_count(), 0);
+    }
+}
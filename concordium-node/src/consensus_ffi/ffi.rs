@@ -18,6 +18,7 @@ use concordium_base::{
     common::Serial,
     hashes::{BlockHash, TransactionHash},
 };
+use prometheus::HistogramVec;
 use std::{
     convert::{TryFrom, TryInto},
     ffi::{CStr, CString},
@@ -1445,6 +1446,8 @@ pub struct StartConsensusConfig {
     pub notification_context:       Option<NotificationContext>,
     /// Context for when signalling a unsupported protocol update is pending.
     pub unsupported_update_context: Option<NotifyUnsupportedUpdatesContext>,
+    /// The histogram recording the duration of the calls into consensus.
+    pub call_duration:              Option<HistogramVec>,
 }
 
 pub fn get_consensus_ptr(
@@ -1555,80 +1558,94 @@ impl ConsensusContainer {
         genesis_index: u32,
         block: &[u8],
     ) -> (ConsensusFfiResponse, Option<ExecuteBlockCallback>) {
-        let consensus = self.consensus.load(Ordering::SeqCst);
+        self.timed("receive_block", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+
+            let mut ptr_block_to_execute = std::ptr::null_mut();
+            let ptr_ptr_block_to_execute = &mut ptr_block_to_execute;
+            let result = unsafe {
+                receiveBlock(
+                    consensus,
+                    genesis_index,
+                    block.as_ptr(),
+                    block.len() as u64,
+                    ptr_ptr_block_to_execute,
+                )
+            };
+            let callback = if ptr_block_to_execute.is_null() {
+                None
+            } else {
+                Some(ExecuteBlockCallback(ptr_block_to_execute))
+            };
 
-        let mut ptr_block_to_execute = std::ptr::null_mut();
-        let ptr_ptr_block_to_execute = &mut ptr_block_to_execute;
-        let result = unsafe {
-            receiveBlock(
-                consensus,
-                genesis_index,
-                block.as_ptr(),
-                block.len() as u64,
-                ptr_ptr_block_to_execute,
+            (
+                ConsensusFfiResponse::try_from(result)
+                    .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code)),
+                callback,
             )
-        };
-        let callback = if ptr_block_to_execute.is_null() {
-            None
-        } else {
-            Some(ExecuteBlockCallback(ptr_block_to_execute))
-        };
-
-        (
-            ConsensusFfiResponse::try_from(result)
-                .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code)),
-            callback,
-        )
+        })
     }
 
     pub fn execute_block(
         &self,
         execute_block_callback: ExecuteBlockCallback,
     ) -> ConsensusFfiResponse {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let result = unsafe { executeBlock(consensus, execute_block_callback) };
-        ConsensusFfiResponse::try_from(result)
-            .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code))
+        self.timed("execute_block", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let result = unsafe { executeBlock(consensus, execute_block_callback) };
+            ConsensusFfiResponse::try_from(result)
+                .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code))
+        })
     }
 
     pub fn send_finalization(&self, genesis_index: u32, msg: &[u8]) -> ConsensusFfiResponse {
-        wrap_send_data_to_c!(self, genesis_index, msg, receiveFinalizationMessage)
+        self.timed("send_finalization", || {
+            wrap_send_data_to_c!(self, genesis_index, msg, receiveFinalizationMessage)
+        })
     }
 
     pub fn send_finalization_record(&self, genesis_index: u32, rec: &[u8]) -> ConsensusFfiResponse {
-        wrap_send_data_to_c!(self, genesis_index, rec, receiveFinalizationRecord)
+        self.timed("send_finalization_record", || {
+            wrap_send_data_to_c!(self, genesis_index, rec, receiveFinalizationRecord)
+        })
     }
 
     /// Send a transaction to consensus. Return whether the operation succeeded
     /// or not, and if the transaction is accepted by consensus then its
     /// hash is returned.
     pub fn send_transaction(&self, data: &[u8]) -> (Option<TransactionHash>, ConsensusFfiResponse) {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let len = data.len();
-        let mut out_hash = [0u8; 32];
-        let result = unsafe {
-            receiveTransaction(consensus, data.as_ptr(), len as i64, out_hash.as_mut_ptr())
-        };
+        self.timed("send_transaction", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let len = data.len();
+            let mut out_hash = [0u8; 32];
+            let result = unsafe {
+                receiveTransaction(consensus, data.as_ptr(), len as i64, out_hash.as_mut_ptr())
+            };
 
-        let return_code = ConsensusFfiResponse::try_from(result)
-            .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code));
-        if return_code == ConsensusFfiResponse::Success {
-            (Some(out_hash.into()), return_code)
-        } else {
-            (None, return_code)
-        }
+            let return_code = ConsensusFfiResponse::try_from(result)
+                .unwrap_or_else(|code| panic!("Unknown FFI return code: {}", code));
+            if return_code == ConsensusFfiResponse::Success {
+                (Some(out_hash.into()), return_code)
+            } else {
+                (None, return_code)
+            }
+        })
     }
 
     pub fn get_consensus_status(&self) -> String {
-        wrap_c_call_string!(self, consensus, |consensus| getConsensusStatus(consensus))
+        self.timed("get_consensus_status", || {
+            wrap_c_call_string!(self, consensus, |consensus| getConsensusStatus(consensus))
+        })
     }
 
     pub fn get_block_info(&self, block_hash: &str) -> anyhow::Result<String> {
-        let c_str = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getBlockInfo(
-            consensus,
-            c_str.as_ptr()
-        )))
+        self.timed("get_block_info", || {
+            let c_str = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getBlockInfo(
+                consensus,
+                c_str.as_ptr()
+            )))
+        })
     }
 
     pub fn get_blocks_at_height(
@@ -1637,52 +1654,66 @@ impl ConsensusContainer {
         genesis_index: u32,
         restrict: bool,
     ) -> String {
-        wrap_c_call_string!(self, consensus, |consensus| getBlocksAtHeight(
-            consensus,
-            block_height,
-            genesis_index,
-            restrict as u8
-        ))
+        self.timed("get_blocks_at_height", || {
+            wrap_c_call_string!(self, consensus, |consensus| getBlocksAtHeight(
+                consensus,
+                block_height,
+                genesis_index,
+                restrict as u8
+            ))
+        })
     }
 
     pub fn get_last_finalized_block_height(&self) -> u64 {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        unsafe { getLastFinalizedBlockHeight(consensus) }
+        self.timed("get_last_finalized_block_height", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            unsafe { getLastFinalizedBlockHeight(consensus) }
+        })
     }
 
     pub fn get_ancestors(&self, block_hash: &str, amount: u64) -> anyhow::Result<String> {
-        let c_str = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getAncestors(
-            consensus,
-            c_str.as_ptr(),
-            amount
-        )))
+        self.timed("get_ancestors", || {
+            let c_str = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getAncestors(
+                consensus,
+                c_str.as_ptr(),
+                amount
+            )))
+        })
     }
 
     pub fn get_branches(&self) -> String {
-        wrap_c_call_string!(self, consensus, |consensus| getBranches(consensus))
+        self.timed("get_branches", || {
+            wrap_c_call_string!(self, consensus, |consensus| getBranches(consensus))
+        })
     }
 
     /// Get the total number of non-finalized transactions across all accounts.
     pub fn number_of_non_finalized_transactions(&self) -> u64 {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        unsafe { getNumberOfNonFinalizedTransactions(consensus) }
+        self.timed("number_of_non_finalized_transactions", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            unsafe { getNumberOfNonFinalizedTransactions(consensus) }
+        })
     }
 
     pub fn get_account_list(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getAccountList(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_account_list", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getAccountList(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_instances(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getInstances(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_instances", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getInstances(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_account_info(
@@ -1690,13 +1721,15 @@ impl ConsensusContainer {
         block_hash: &str,
         account_address: &str,
     ) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        let account_address = CString::new(account_address)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getAccountInfo(
-            consensus,
-            block_hash.as_ptr(),
-            account_address.as_ptr()
-        )))
+        self.timed("get_account_info", || {
+            let block_hash = CString::new(block_hash)?;
+            let account_address = CString::new(account_address)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getAccountInfo(
+                consensus,
+                block_hash.as_ptr(),
+                account_address.as_ptr()
+            )))
+        })
     }
 
     pub fn get_instance_info(
@@ -1704,13 +1737,15 @@ impl ConsensusContainer {
         block_hash: &str,
         contract_address: &str,
     ) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        let contract_address = CString::new(contract_address)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getInstanceInfo(
-            consensus,
-            block_hash.as_ptr(),
-            contract_address.as_ptr()
-        )))
+        self.timed("get_instance_info", || {
+            let block_hash = CString::new(block_hash)?;
+            let contract_address = CString::new(contract_address)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getInstanceInfo(
+                consensus,
+                block_hash.as_ptr(),
+                contract_address.as_ptr()
+            )))
+        })
     }
 
     pub fn invoke_contract(
@@ -1719,56 +1754,68 @@ impl ConsensusContainer {
         context: &str,
         invoke_max_energy: u64,
     ) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        let context = CString::new(context)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| invokeContract(
-            consensus,
-            block_hash.as_ptr(),
-            context.as_ptr(),
-            invoke_max_energy
-        )))
+        self.timed("invoke_contract", || {
+            let block_hash = CString::new(block_hash)?;
+            let context = CString::new(context)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| invokeContract(
+                consensus,
+                block_hash.as_ptr(),
+                context.as_ptr(),
+                invoke_max_energy
+            )))
+        })
     }
 
     pub fn get_reward_status(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getRewardStatus(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_reward_status", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getRewardStatus(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_birk_parameters(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getBirkParameters(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_birk_parameters", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getBirkParameters(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_module_list(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getModuleList(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_module_list", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getModuleList(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_module_source(&self, block_hash: &str, module_ref: &str) -> anyhow::Result<Vec<u8>> {
-        let block_hash = CString::new(block_hash)?;
-        let module_ref = CString::new(module_ref)?;
-        Ok(wrap_c_call_bytes!(self, |consensus| getModuleSource(
-            consensus,
-            block_hash.as_ptr(),
-            module_ref.as_ptr()
-        )))
+        self.timed("get_module_source", || {
+            let block_hash = CString::new(block_hash)?;
+            let module_ref = CString::new(module_ref)?;
+            Ok(wrap_c_call_bytes!(self, |consensus| getModuleSource(
+                consensus,
+                block_hash.as_ptr(),
+                module_ref.as_ptr()
+            )))
+        })
     }
 
     pub fn get_baker_list(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getBakerList(
-            consensus,
-            block_hash.as_ptr() as *const u8
-        )))
+        self.timed("get_baker_list", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getBakerList(
+                consensus,
+                block_hash.as_ptr() as *const u8
+            )))
+        })
     }
 
     pub fn get_pool_status(
@@ -1777,33 +1824,37 @@ impl ConsensusContainer {
         passive_delegation: bool,
         baker_id: u64,
     ) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getPoolStatus(
-            consensus,
-            block_hash.as_ptr() as *const u8,
-            passive_delegation,
-            baker_id,
-        )))
+        self.timed("get_pool_status", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getPoolStatus(
+                consensus,
+                block_hash.as_ptr() as *const u8,
+                passive_delegation,
+                baker_id,
+            )))
+        })
     }
 
     /// Construct a catch-up request message. The message includes the packet
     /// type byte and genesis index.
     pub fn get_catch_up_status(&self) -> Arc<[u8]> {
-        let consensus = self.consensus.load(Ordering::SeqCst);
+        self.timed("get_catch_up_status", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
 
-        unsafe {
-            let mut genesis_index: u32 = 0;
-            let mut message_bytes: *const u8 = ptr::null();
-            let message_length =
-                getCatchUpStatus(consensus, &mut genesis_index, &mut message_bytes);
-            let slice = &slice::from_raw_parts(message_bytes, message_length as usize);
-            let mut ret = Vec::with_capacity(5 + slice.len());
-            ret.extend_from_slice(&(PacketType::CatchUpStatus as u8).to_be_bytes());
-            ret.extend_from_slice(&genesis_index.to_be_bytes());
-            ret.extend_from_slice(slice);
-            freeCStr(message_bytes as *const i8);
-            Arc::from(ret)
-        }
+            unsafe {
+                let mut genesis_index: u32 = 0;
+                let mut message_bytes: *const u8 = ptr::null();
+                let message_length =
+                    getCatchUpStatus(consensus, &mut genesis_index, &mut message_bytes);
+                let slice = &slice::from_raw_parts(message_bytes, message_length as usize);
+                let mut ret = Vec::with_capacity(5 + slice.len());
+                ret.extend_from_slice(&(PacketType::CatchUpStatus as u8).to_be_bytes());
+                ret.extend_from_slice(&genesis_index.to_be_bytes());
+                ret.extend_from_slice(slice);
+                freeCStr(message_bytes as *const i8);
+                Arc::from(ret)
+            }
+        })
     }
 
     pub fn receive_catch_up_status(
@@ -1813,15 +1864,17 @@ impl ConsensusContainer {
         peer_id: RemotePeerId,
         object_limit: i64,
     ) -> ConsensusFfiResponse {
-        wrap_c_call!(self, |consensus| receiveCatchUpStatus(
-            consensus,
-            peer_id.into(),
-            genesis_index,
-            request.as_ptr(),
-            request.len() as i64,
-            object_limit,
-            direct_callback
-        ))
+        self.timed("receive_catch_up_status", || {
+            wrap_c_call!(self, |consensus| receiveCatchUpStatus(
+                consensus,
+                peer_id.into(),
+                genesis_index,
+                request.as_ptr(),
+                request.len() as i64,
+                object_limit,
+                direct_callback
+            ))
+        })
     }
 
     /// Gets baker status of the node along with the baker ID
@@ -1832,61 +1885,74 @@ impl ConsensusContainer {
     /// natural, because a weird issue on Windows caused node_info to
     /// produce the wrong result.
     pub fn in_baking_committee(&self) -> (ConsensusIsInBakingCommitteeResponse, bool, u64, f64) {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut baker_id: u64 = 0;
-        let mut has_baker_id: u8 = 0;
-        let mut baker_lottery_power: f64 = 0.0;
-
-        let result = unsafe {
-            bakerStatusBestBlock(
-                consensus,
-                &mut baker_id,
-                &mut has_baker_id,
-                &mut baker_lottery_power,
-            )
-        };
+        self.timed("in_baking_committee", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut baker_id: u64 = 0;
+            let mut has_baker_id: u8 = 0;
+            let mut baker_lottery_power: f64 = 0.0;
+
+            let result = unsafe {
+                bakerStatusBestBlock(
+                    consensus,
+                    &mut baker_id,
+                    &mut has_baker_id,
+                    &mut baker_lottery_power,
+                )
+            };
 
-        let status = ConsensusIsInBakingCommitteeResponse::try_from(result).unwrap_or_else(|err| {
-            unreachable!("An error occured when trying to convert FFI return code: {}", err)
-        });
+            let status =
+                ConsensusIsInBakingCommitteeResponse::try_from(result).unwrap_or_else(|err| {
+                    unreachable!("An error occured when trying to convert FFI return code: {}", err)
+                });
 
-        (status, has_baker_id != 0, baker_id, baker_lottery_power)
+            (status, has_baker_id != 0, baker_id, baker_lottery_power)
+        })
     }
 
     pub fn in_finalization_committee(&self) -> bool {
-        wrap_c_bool_call!(self, |consensus| checkIfWeAreFinalizer(consensus))
+        self.timed("in_finalization_committee", || {
+            wrap_c_bool_call!(self, |consensus| checkIfWeAreFinalizer(consensus))
+        })
     }
 
     /// Checks if consensus is running, i.e. if consensus has been shut down,
     /// this will return false.
     pub fn is_consensus_running(&self) -> bool {
-        wrap_c_bool_call!(self, |consensus| checkIfRunning(consensus))
+        self.timed("is_consensus_running", || {
+            wrap_c_bool_call!(self, |consensus| checkIfRunning(consensus))
+        })
     }
 
     pub fn get_account_non_finalized_transactions(
         &self,
         account_address: &str,
     ) -> anyhow::Result<String> {
-        let account_address = CString::new(account_address)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| {
-            getAccountNonFinalizedTransactions(consensus, account_address.as_ptr())
-        }))
+        self.timed("get_account_non_finalized_transactions", || {
+            let account_address = CString::new(account_address)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| {
+                getAccountNonFinalizedTransactions(consensus, account_address.as_ptr())
+            }))
+        })
     }
 
     pub fn get_block_summary(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getBlockSummary(
-            consensus,
-            block_hash.as_ptr()
-        )))
+        self.timed("get_block_summary", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getBlockSummary(
+                consensus,
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_transaction_status(&self, transaction_hash: &str) -> anyhow::Result<String> {
-        let transaction_hash = CString::new(transaction_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getTransactionStatus(
-            consensus,
-            transaction_hash.as_ptr()
-        )))
+        self.timed("get_transaction_status", || {
+            let transaction_hash = CString::new(transaction_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getTransactionStatus(
+                consensus,
+                transaction_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_transaction_status_in_block(
@@ -1894,69 +1960,83 @@ impl ConsensusContainer {
         transaction_hash: &str,
         block_hash: &str,
     ) -> anyhow::Result<String> {
-        let transaction_hash = CString::new(transaction_hash)?;
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getTransactionStatusInBlock(
-            consensus,
-            transaction_hash.as_ptr(),
-            block_hash.as_ptr()
-        )))
+        self.timed("get_transaction_status_in_block", || {
+            let transaction_hash = CString::new(transaction_hash)?;
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getTransactionStatusInBlock(
+                consensus,
+                transaction_hash.as_ptr(),
+                block_hash.as_ptr()
+            )))
+        })
     }
 
     pub fn get_next_account_nonce(&self, account_address: &str) -> anyhow::Result<String> {
-        let account_address = CString::new(account_address)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getNextAccountNonce(
-            consensus,
-            account_address.as_ptr(),
-        )))
+        self.timed("get_next_account_nonce", || {
+            let account_address = CString::new(account_address)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getNextAccountNonce(
+                consensus,
+                account_address.as_ptr(),
+            )))
+        })
     }
 
     pub fn get_identity_providers(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getAllIdentityProviders(
-            consensus,
-            block_hash.as_ptr(),
-        )))
+        self.timed("get_identity_providers", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getAllIdentityProviders(
+                consensus,
+                block_hash.as_ptr(),
+            )))
+        })
     }
 
     pub fn get_anonymity_revokers(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getAllAnonymityRevokers(
-            consensus,
-            block_hash.as_ptr(),
-        )))
+        self.timed("get_anonymity_revokers", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getAllAnonymityRevokers(
+                consensus,
+                block_hash.as_ptr(),
+            )))
+        })
     }
 
     pub fn get_cryptographic_parameters(&self, block_hash: &str) -> anyhow::Result<String> {
-        let block_hash = CString::new(block_hash)?;
-        Ok(wrap_c_call_string!(self, consensus, |consensus| getCryptographicParameters(
-            consensus,
-            block_hash.as_ptr(),
-        )))
+        self.timed("get_cryptographic_parameters", || {
+            let block_hash = CString::new(block_hash)?;
+            Ok(wrap_c_call_string!(self, consensus, |consensus| getCryptographicParameters(
+                consensus,
+                block_hash.as_ptr(),
+            )))
+        })
     }
 
     /// Import blocks from the given file path. If the file exists and the node
     /// could import all blocks from the file `Ok(())` is returned. Otherwise an
     /// error is returned.
     pub fn import_blocks(&self, import_file_path: &Path) -> anyhow::Result<()> {
-        let consensus = self.consensus.load(Ordering::SeqCst);
+        self.timed("import_blocks", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
 
-        let path_bytes =
-            import_file_path.as_os_str().to_str().context("Cannot decode path.")?.as_bytes();
+            let path_bytes =
+                import_file_path.as_os_str().to_str().context("Cannot decode path.")?.as_bytes();
 
-        let len = path_bytes.len();
+            let len = path_bytes.len();
 
-        let response = unsafe { importBlocks(consensus, path_bytes.as_ptr(), len as i64) };
-        match ConsensusFfiResponse::try_from(response)? {
-            ConsensusFfiResponse::Success => Ok(()),
-            other => bail!("Error during block import: {}", other),
-        }
+            let response = unsafe { importBlocks(consensus, path_bytes.as_ptr(), len as i64) };
+            match ConsensusFfiResponse::try_from(response)? {
+                ConsensusFfiResponse::Success => Ok(()),
+                other => bail!("Error during block import: {}", other),
+            }
+        })
     }
 
     pub fn stop_importing_blocks(&self) {
-        let consensus = self.consensus.load(Ordering::SeqCst);
+        self.timed("stop_importing_blocks", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
 
-        unsafe { stopImportingBlocks(consensus) }
+            unsafe { stopImportingBlocks(consensus) }
+        })
     }
 
     /// Look up the account in the given block.
@@ -1970,29 +2050,31 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         account_identifier: &crate::grpc2::types::AccountIdentifierInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let (acc_type, acc_id) =
-            crate::grpc2::types::account_identifier_to_ffi(account_identifier).require()?;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let response: ConsensusQueryResponse = unsafe {
-            getAccountInfoV2(
-                consensus,
-                block_id_type,
-                block_hash,
-                acc_type,
-                acc_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-            .try_into()?
-        };
-        response.ensure_ok("account or block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_account_info_v2", || {
+            use crate::grpc2::Require;
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let (acc_type, acc_id) =
+                crate::grpc2::types::account_identifier_to_ffi(account_identifier).require()?;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let response: ConsensusQueryResponse = unsafe {
+                getAccountInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_hash,
+                    acc_type,
+                    acc_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+                .try_into()?
+            };
+            response.ensure_ok("account or block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get the best guess as to what the next account sequence number should
@@ -2003,33 +2085,37 @@ impl ConsensusContainer {
         &self,
         account_address: &crate::grpc2::types::AccountAddress,
     ) -> Result<Vec<u8>, tonic::Status> {
-        use crate::grpc2::Require;
-        let account_address_ptr =
-            crate::grpc2::types::account_address_to_ffi(account_address).require()?;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let _response: ConsensusQueryResponse = unsafe {
-            getNextAccountSequenceNumberV2(
-                consensus,
-                account_address_ptr,
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-            .try_into()?
-        };
-        // The query should always return successfully, so no need to check here.
-        Ok(out_data)
+        self.timed("get_next_account_sequence_number_v2", || {
+            use crate::grpc2::Require;
+            let account_address_ptr =
+                crate::grpc2::types::account_address_to_ffi(account_address).require()?;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let _response: ConsensusQueryResponse = unsafe {
+                getNextAccountSequenceNumberV2(
+                    consensus,
+                    account_address_ptr,
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+                .try_into()?
+            };
+            // The query should always return successfully, so no need to check here.
+            Ok(out_data)
+        })
     }
 
     /// Get information of the current state of consensus.
     pub fn get_consensus_info_v2(&self) -> Result<Vec<u8>, tonic::Status> {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let _response: ConsensusQueryResponse = unsafe {
-            getConsensusInfoV2(consensus, &mut out_data, copy_to_vec_callback).try_into()?
-        };
-        // The query should always return successfully, so no need to check here.
-        Ok(out_data)
+        self.timed("get_consensus_info_v2", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let _response: ConsensusQueryResponse = unsafe {
+                getConsensusInfoV2(consensus, &mut out_data, copy_to_vec_callback).try_into()?
+            };
+            // The query should always return successfully, so no need to check here.
+            Ok(out_data)
+        })
     }
 
     /// Get the cryptographic parameters in a given block.
@@ -2037,37 +2123,40 @@ impl ConsensusContainer {
         &self,
         block_hash: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], crate::grpc2::types::CryptographicParameters), tonic::Status> {
-        use crate::grpc2::Require;
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_hash = [0u8; 32];
-        let mut crypto_parameter_ptr: Option<CryptographicParameters> = None;
-        let response: ConsensusQueryResponse = unsafe {
-            getCryptographicParametersV2(
-                consensus,
-                block_id_type,
-                block_hash,
-                out_hash.as_mut_ptr(),
-                &mut crypto_parameter_ptr,
-                copy_cryptographic_parameters_callback,
-            )
-            .try_into()?
-        };
-        response.ensure_ok("block")?;
-        let crypto_parameters = crypto_parameter_ptr
-            .ok_or_else(|| tonic::Status::internal("Failed to access cryptographic parameters"))?;
-
-        let out = crate::grpc2::types::CryptographicParameters {
-            genesis_string:          crypto_parameters.genesis_string.clone(),
-            bulletproof_generators:  concordium_base::common::to_bytes(
-                crypto_parameters.bulletproof_generators(),
-            ),
-            on_chain_commitment_key: concordium_base::common::to_bytes(
-                &crypto_parameters.on_chain_commitment_key,
-            ),
-        };
-        Ok((out_hash, out))
+        self.timed("get_cryptographic_parameters_v2", || {
+            use crate::grpc2::Require;
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_hash = [0u8; 32];
+            let mut crypto_parameter_ptr: Option<CryptographicParameters> = None;
+            let response: ConsensusQueryResponse = unsafe {
+                getCryptographicParametersV2(
+                    consensus,
+                    block_id_type,
+                    block_hash,
+                    out_hash.as_mut_ptr(),
+                    &mut crypto_parameter_ptr,
+                    copy_cryptographic_parameters_callback,
+                )
+                .try_into()?
+            };
+            response.ensure_ok("block")?;
+            let crypto_parameters = crypto_parameter_ptr.ok_or_else(|| {
+                tonic::Status::internal("Failed to access cryptographic parameters")
+            })?;
+
+            let out = crate::grpc2::types::CryptographicParameters {
+                genesis_string:          crypto_parameters.genesis_string.clone(),
+                bulletproof_generators:  concordium_base::common::to_bytes(
+                    crypto_parameters.bulletproof_generators(),
+                ),
+                on_chain_commitment_key: concordium_base::common::to_bytes(
+                    &crypto_parameters.on_chain_commitment_key,
+                ),
+            };
+            Ok((out_hash, out))
+        })
     }
 
     /// Look up accounts in the given block, and return a stream of their
@@ -2080,30 +2169,32 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let sender_ptr = Box::into_raw(sender);
-        let response: ConsensusQueryResponse = unsafe {
-            getAccountListV2(
-                consensus,
-                sender_ptr,
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        if let Err(e) = response.ensure_ok("block") {
-            let _ = unsafe { Box::from_raw(sender_ptr) }; // deallocate sender since it is unused by Haskell.
-            Err(e)
-        } else {
-            Ok(buf)
-        }
+        self.timed("get_account_list_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let sender_ptr = Box::into_raw(sender);
+            let response: ConsensusQueryResponse = unsafe {
+                getAccountListV2(
+                    consensus,
+                    sender_ptr,
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            if let Err(e) = response.ensure_ok("block") {
+                let _ = unsafe { Box::from_raw(sender_ptr) }; // deallocate sender since it is unused by Haskell.
+                Err(e)
+            } else {
+                Ok(buf)
+            }
+        })
     }
 
     /// Get a list of all smart contract modules. The stream will end
@@ -2114,26 +2205,28 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getModuleListV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_module_list_v2", || {
+            use crate::grpc2::Require;
+
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getModuleListV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get the source of a smart contract module.
@@ -2142,27 +2235,30 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         module_ref: &crate::grpc2::types::ModuleRef,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let module_ref_ptr = crate::grpc2::types::module_reference_to_ffi(module_ref).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getModuleSourceV2(
-                consensus,
-                block_id_type,
-                block_id,
-                module_ref_ptr,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("module or block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_module_source_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let module_ref_ptr =
+                crate::grpc2::types::module_reference_to_ffi(module_ref).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getModuleSourceV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    module_ref_ptr,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("module or block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get a list of addresses for all smart contract instances. The stream
@@ -2173,25 +2269,27 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getInstanceListV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_instance_list_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getInstanceListV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get information about a specific smart contract instance.
@@ -2200,29 +2298,31 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         address: &crate::grpc2::types::ContractAddress,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let addr_index = address.index;
-        let addr_subindex = address.subindex;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let response: ConsensusQueryResponse = unsafe {
-            getInstanceInfoV2(
-                consensus,
-                block_id_type,
-                block_hash,
-                addr_index,
-                addr_subindex,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-            .try_into()?
-        };
-        response.ensure_ok("block or instance")?;
-        Ok((out_hash, out_data))
+        self.timed("get_instance_info_v2", || {
+            use crate::grpc2::Require;
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let addr_index = address.index;
+            let addr_subindex = address.subindex;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let response: ConsensusQueryResponse = unsafe {
+                getInstanceInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_hash,
+                    addr_index,
+                    addr_subindex,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+                .try_into()?
+            };
+            response.ensure_ok("block or instance")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get the entire smart contract state of the specified instance.
@@ -2231,40 +2331,42 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         address: &crate::grpc2::types::ContractAddress,
     ) -> Result<([u8; 32], ContractStateResponse), tonic::Status> {
-        use crate::grpc2::Require;
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let addr_index = address.index;
-        let addr_subindex = address.subindex;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_v0_data: Vec<u8> = Vec::new();
-        let mut out_v1_data = None;
-        let mut out_hash = [0u8; 32];
-        let response: ConsensusQueryResponse = unsafe {
-            getInstanceStateV2(
-                consensus,
-                block_id_type,
-                block_hash,
-                addr_index,
-                addr_subindex,
-                out_hash.as_mut_ptr(),
-                &mut out_v0_data,
-                copy_to_vec_callback,
-                &mut out_v1_data,
-                copy_v1_contract_state_callback,
-            )
-            .try_into()?
-        };
-        response.ensure_ok("block or instance")?;
-        match out_v1_data {
-            None => Ok((out_hash, ContractStateResponse::V0 {
-                state: out_v0_data,
-            })),
-            Some(data) => Ok((out_hash, ContractStateResponse::V1 {
-                state:  data.state,
-                loader: data.loader,
-            })),
-        }
+        self.timed("get_instance_state_v2", || {
+            use crate::grpc2::Require;
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let addr_index = address.index;
+            let addr_subindex = address.subindex;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_v0_data: Vec<u8> = Vec::new();
+            let mut out_v1_data = None;
+            let mut out_hash = [0u8; 32];
+            let response: ConsensusQueryResponse = unsafe {
+                getInstanceStateV2(
+                    consensus,
+                    block_id_type,
+                    block_hash,
+                    addr_index,
+                    addr_subindex,
+                    out_hash.as_mut_ptr(),
+                    &mut out_v0_data,
+                    copy_to_vec_callback,
+                    &mut out_v1_data,
+                    copy_v1_contract_state_callback,
+                )
+                .try_into()?
+            };
+            response.ensure_ok("block or instance")?;
+            match out_v1_data {
+                None => Ok((out_hash, ContractStateResponse::V0 {
+                    state: out_v0_data,
+                })),
+                Some(data) => Ok((out_hash, ContractStateResponse::V1 {
+                    state:  data.state,
+                    loader: data.loader,
+                })),
+            }
+        })
     }
 
     /// Get ancestors for the provided block.
@@ -2274,26 +2376,28 @@ impl ConsensusContainer {
         amount: u64,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getAncestorsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                amount,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_ancestors_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getAncestorsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    amount,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get information about a specific transaction.
@@ -2301,22 +2405,24 @@ impl ConsensusContainer {
         &self,
         transaction_hash: &crate::grpc2::types::TransactionHash,
     ) -> Result<Vec<u8>, tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let transaction_hash_ptr =
-            crate::grpc2::types::transaction_hash_to_ffi(transaction_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockItemStatusV2(
-                consensus,
-                transaction_hash_ptr,
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-            .try_into()?
-        };
-        response.ensure_ok("transaction")?;
-        Ok(out_data)
+        self.timed("get_block_item_status_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let transaction_hash_ptr =
+                crate::grpc2::types::transaction_hash_to_ffi(transaction_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockItemStatusV2(
+                    consensus,
+                    transaction_hash_ptr,
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+                .try_into()?
+            };
+            response.ensure_ok("transaction")?;
+            Ok(out_data)
+        })
     }
 
     /// Run the smart contract entrypoint in a given context and in the state at
@@ -2326,79 +2432,82 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::InvokeInstanceRequest,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request.block_hash.as_ref().require()?)
-                .require()?;
-
-        // Optional Address to ffi
-        let (
-            invoker_address_type,
-            invoker_account_address_ptr,
-            invoker_contract_index,
-            invoker_contract_subindex,
-        ) = if let Some(address) = &request.invoker {
-            match address.r#type.as_ref().require()? {
-                crate::grpc2::types::address::Type::Account(account) => {
-                    (1, crate::grpc2::types::account_address_to_ffi(account).require()?, 0, 0)
-                }
-                crate::grpc2::types::address::Type::Contract(contract) => {
-                    (2, std::ptr::null(), contract.index, contract.subindex)
-                }
-            }
-        } else {
-            (0, std::ptr::null(), 0, 0)
-        };
-
-        let amount = request.amount.as_ref().require()?.value;
-
-        let (receive_name_ptr, receive_name_len) =
-            crate::grpc2::types::receive_name_to_ffi(request.entrypoint.as_ref().require()?)
-                .require()?;
-
-        // Parameter to ffi
-        let (parameter_ptr, parameter_len) = {
-            let bytes = &request.parameter.as_ref().require()?.value;
-            (
-                bytes.as_ptr(),
-                bytes.len().try_into().map_err(|_| {
-                    tonic::Status::invalid_argument("Parameter exceeds maximum supported size.")
-                })?,
+        self.timed("invoke_instance_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) = crate::grpc2::types::block_hash_input_to_ffi(
+                request.block_hash.as_ref().require()?,
             )
-        };
-
-        let energy = request.energy.as_ref().require()?.value;
+            .require()?;
 
-        let contract = request.instance.as_ref().require()?;
-
-        let response: ConsensusQueryResponse = unsafe {
-            invokeInstanceV2(
-                consensus,
-                block_id_type,
-                block_id,
-                contract.index,
-                contract.subindex,
+            // Optional Address to ffi
+            let (
                 invoker_address_type,
                 invoker_account_address_ptr,
                 invoker_contract_index,
                 invoker_contract_subindex,
-                amount,
-                receive_name_ptr,
-                receive_name_len,
-                parameter_ptr,
-                parameter_len,
-                energy,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block or contract")?;
-        Ok((out_hash, out_data))
+            ) = if let Some(address) = &request.invoker {
+                match address.r#type.as_ref().require()? {
+                    crate::grpc2::types::address::Type::Account(account) => {
+                        (1, crate::grpc2::types::account_address_to_ffi(account).require()?, 0, 0)
+                    }
+                    crate::grpc2::types::address::Type::Contract(contract) => {
+                        (2, std::ptr::null(), contract.index, contract.subindex)
+                    }
+                }
+            } else {
+                (0, std::ptr::null(), 0, 0)
+            };
+
+            let amount = request.amount.as_ref().require()?.value;
+
+            let (receive_name_ptr, receive_name_len) =
+                crate::grpc2::types::receive_name_to_ffi(request.entrypoint.as_ref().require()?)
+                    .require()?;
+
+            // Parameter to ffi
+            let (parameter_ptr, parameter_len) = {
+                let bytes = &request.parameter.as_ref().require()?.value;
+                (
+                    bytes.as_ptr(),
+                    bytes.len().try_into().map_err(|_| {
+                        tonic::Status::invalid_argument("Parameter exceeds maximum supported size.")
+                    })?,
+                )
+            };
+
+            let energy = request.energy.as_ref().require()?.value;
+
+            let contract = request.instance.as_ref().require()?;
+
+            let response: ConsensusQueryResponse = unsafe {
+                invokeInstanceV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    contract.index,
+                    contract.subindex,
+                    invoker_address_type,
+                    invoker_account_address_ptr,
+                    invoker_contract_index,
+                    invoker_contract_subindex,
+                    amount,
+                    receive_name_ptr,
+                    receive_name_len,
+                    parameter_ptr,
+                    parameter_len,
+                    energy,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block or contract")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get information, such as height, timings, and transaction counts for the
@@ -2407,25 +2516,27 @@ impl ConsensusContainer {
         &self,
         block_hash: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockInfoV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_block_info_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get a list bakers at the end of a given block. The stream will end when
@@ -2435,25 +2546,27 @@ impl ConsensusContainer {
         block_hash: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBakerListV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_baker_list_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBakerListV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get status information about a given pool at the end of a given block.
@@ -2461,29 +2574,32 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::PoolInfoRequest,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request.block_hash.as_ref().require()?)
-                .require()?;
-        let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
-
-        let response: ConsensusQueryResponse = unsafe {
-            getPoolInfoV2(
-                consensus,
-                block_id_type,
-                block_id,
-                baker_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
+        self.timed("get_pool_info_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) = crate::grpc2::types::block_hash_input_to_ffi(
+                request.block_hash.as_ref().require()?,
             )
-        }
-        .try_into()?;
-        response.ensure_ok("block or baker")?;
-        Ok((out_hash, out_data))
+            .require()?;
+            let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
+
+            let response: ConsensusQueryResponse = unsafe {
+                getPoolInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    baker_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block or baker")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get status information about the passive delegators at the end of a
@@ -2492,25 +2608,27 @@ impl ConsensusContainer {
         &self,
         block_hash: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getPassiveDelegationInfoV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_passive_delegation_info_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getPassiveDelegationInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get a stream of live blocks at a given height.
@@ -2518,26 +2636,28 @@ impl ConsensusContainer {
         &self,
         height: &crate::grpc2::types::BlocksAtHeightRequest,
     ) -> Result<Vec<u8>, tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-
-        let (block_height, genesis_index, restrict) =
-            crate::grpc2::types::blocks_at_height_request_to_ffi(height).require()?;
-
-        let mut out_data: Vec<u8> = Vec::new();
-        let _response: ConsensusQueryResponse = unsafe {
-            getBlocksAtHeightV2(
-                consensus,
-                block_height,
-                genesis_index,
-                restrict,
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        // The query should always return successfully, so no need to check here.
-        Ok(out_data)
+        self.timed("get_blocks_at_height_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+
+            let (block_height, genesis_index, restrict) =
+                crate::grpc2::types::blocks_at_height_request_to_ffi(height).require()?;
+
+            let mut out_data: Vec<u8> = Vec::new();
+            let _response: ConsensusQueryResponse = unsafe {
+                getBlocksAtHeightV2(
+                    consensus,
+                    block_height,
+                    genesis_index,
+                    restrict,
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            // The query should always return successfully, so no need to check here.
+            Ok(out_data)
+        })
     }
 
     /// Get status of the tokenomics at the end of a given block.
@@ -2545,25 +2665,27 @@ impl ConsensusContainer {
         &self,
         block_hash: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getTokenomicsInfoV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_tokenomics_info_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(block_hash).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getTokenomicsInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get the pool delegators of a given pool at the end of a given block.
@@ -2573,28 +2695,31 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::GetPoolDelegatorsRequest,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request.block_hash.as_ref().require()?)
-                .require()?;
-        let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
-        let response: ConsensusQueryResponse = unsafe {
-            getPoolDelegatorsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                baker_id,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
+        self.timed("get_pool_delegators_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) = crate::grpc2::types::block_hash_input_to_ffi(
+                request.block_hash.as_ref().require()?,
             )
-        }
-        .try_into()?;
-        response.ensure_ok("block or pool")?;
-        Ok(buf)
+            .require()?;
+            let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
+            let response: ConsensusQueryResponse = unsafe {
+                getPoolDelegatorsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    baker_id,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block or pool")?;
+            Ok(buf)
+        })
     }
 
     /// Get the reward period pool delegators of a given pool at the end of a
@@ -2605,28 +2730,31 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::GetPoolDelegatorsRequest,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request.block_hash.as_ref().require()?)
-                .require()?;
-        let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
-        let response: ConsensusQueryResponse = unsafe {
-            getPoolDelegatorsRewardPeriodV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                baker_id,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
+        self.timed("get_pool_delegators_reward_period_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) = crate::grpc2::types::block_hash_input_to_ffi(
+                request.block_hash.as_ref().require()?,
             )
-        }
-        .try_into()?;
-        response.ensure_ok("block or pool")?;
-        Ok(buf)
+            .require()?;
+            let baker_id = crate::grpc2::types::baker_id_to_ffi(request.baker.as_ref().require()?);
+            let response: ConsensusQueryResponse = unsafe {
+                getPoolDelegatorsRewardPeriodV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    baker_id,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block or pool")?;
+            Ok(buf)
+        })
     }
 
     /// Get the passive delegators at the end of a given block.
@@ -2636,25 +2764,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getPassiveDelegatorsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_passive_delegators_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getPassiveDelegatorsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get the reward period pool delegators of a given pool at the end of a
@@ -2665,35 +2795,40 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getPassiveDelegatorsRewardPeriodV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_passive_delegators_reward_period_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getPassiveDelegatorsRewardPeriodV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get the current branches of blocks starting and including from the last
     /// finalized block.
     pub fn get_branches_v2(&self) -> Result<Vec<u8>, tonic::Status> {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let _response: ConsensusQueryResponse =
-            unsafe { getBranchesV2(consensus, &mut out_data, copy_to_vec_callback) }.try_into()?;
-        Ok(out_data)
+        self.timed("get_branches_v2", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let _response: ConsensusQueryResponse =
+                unsafe { getBranchesV2(consensus, &mut out_data, copy_to_vec_callback) }
+                    .try_into()?;
+            Ok(out_data)
+        })
     }
 
     /// Get information related to the baker election for a particular block.
@@ -2701,26 +2836,28 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-
-        let response: ConsensusQueryResponse = unsafe {
-            getElectionInfoV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_election_info_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+
+            let response: ConsensusQueryResponse = unsafe {
+                getElectionInfoV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get the identity providers registered as of the end of a given block.
@@ -2730,25 +2867,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getIdentityProvidersV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_identity_providers_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getIdentityProvidersV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get the anonymity revokers registered as of the end of a given block.
@@ -2758,25 +2897,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getAnonymityRevokersV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_anonymity_revokers_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getAnonymityRevokersV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get a list of non-finalized transaction hashes for a given account.
@@ -2787,21 +2928,24 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::AccountAddress,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<(), tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let account_address_ptr = crate::grpc2::types::account_address_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getAccountNonFinalizedTransactionsV2(
-                consensus,
-                Box::into_raw(sender),
-                account_address_ptr,
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("account address")?;
-        Ok(())
+        self.timed("get_account_non_finalized_transactions_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let account_address_ptr =
+                crate::grpc2::types::account_address_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getAccountNonFinalizedTransactionsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    account_address_ptr,
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("account address")?;
+            Ok(())
+        })
     }
 
     /// Get a list of block items in a block specified by a block hash.
@@ -2810,25 +2954,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let sender = Box::new(sender);
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let mut buf = [0u8; 32];
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockItemsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_block_items_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let sender = Box::new(sender);
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let mut buf = [0u8; 32];
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockItemsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get a list of transaction events in a given block.
@@ -2839,25 +2985,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockTransactionEventsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_block_transaction_events_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockTransactionEventsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get a list of special events in a given block.
@@ -2868,25 +3016,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockSpecialEventsV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_block_special_events_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockSpecialEventsV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get the pending updates to chain parameters at the end of a given block.
@@ -2897,25 +3047,27 @@ impl ConsensusContainer {
         request: &crate::grpc2::types::BlockHashInput,
         sender: futures::channel::mpsc::Sender<Result<Vec<u8>, tonic::Status>>,
     ) -> Result<[u8; 32], tonic::Status> {
-        use crate::grpc2::Require;
-        let sender = Box::new(sender);
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut buf = [0u8; 32];
-        let (block_id_type, block_hash) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockPendingUpdatesV2(
-                consensus,
-                Box::into_raw(sender),
-                block_id_type,
-                block_hash,
-                buf.as_mut_ptr(),
-                enqueue_bytearray_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok(buf)
+        self.timed("get_block_pending_updates_v2", || {
+            use crate::grpc2::Require;
+            let sender = Box::new(sender);
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut buf = [0u8; 32];
+            let (block_id_type, block_hash) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockPendingUpdatesV2(
+                    consensus,
+                    Box::into_raw(sender),
+                    block_id_type,
+                    block_hash,
+                    buf.as_mut_ptr(),
+                    enqueue_bytearray_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok(buf)
+        })
     }
 
     /// Get next available sequence numbers for updating chain parameters after
@@ -2924,26 +3076,28 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-
-        let response: ConsensusQueryResponse = unsafe {
-            getNextUpdateSequenceNumbersV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_next_update_sequence_numbers_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+
+            let response: ConsensusQueryResponse = unsafe {
+                getNextUpdateSequenceNumbersV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get chain parameters for the given block.
@@ -2951,26 +3105,28 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockChainParametersV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_block_chain_parameters_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockChainParametersV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get chain parameters for the given block.
@@ -2978,35 +3134,39 @@ impl ConsensusContainer {
         &self,
         request: &crate::grpc2::types::BlockHashInput,
     ) -> Result<([u8; 32], Vec<u8>), tonic::Status> {
-        use crate::grpc2::Require;
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let mut out_data: Vec<u8> = Vec::new();
-        let mut out_hash = [0u8; 32];
-        let (block_id_type, block_id) =
-            crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
-
-        let response: ConsensusQueryResponse = unsafe {
-            getBlockFinalizationSummaryV2(
-                consensus,
-                block_id_type,
-                block_id,
-                out_hash.as_mut_ptr(),
-                &mut out_data,
-                copy_to_vec_callback,
-            )
-        }
-        .try_into()?;
-        response.ensure_ok("block")?;
-        Ok((out_hash, out_data))
+        self.timed("get_block_finalization_summary_v2", || {
+            use crate::grpc2::Require;
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let mut out_data: Vec<u8> = Vec::new();
+            let mut out_hash = [0u8; 32];
+            let (block_id_type, block_id) =
+                crate::grpc2::types::block_hash_input_to_ffi(request).require()?;
+
+            let response: ConsensusQueryResponse = unsafe {
+                getBlockFinalizationSummaryV2(
+                    consensus,
+                    block_id_type,
+                    block_id,
+                    out_hash.as_mut_ptr(),
+                    &mut out_data,
+                    copy_to_vec_callback,
+                )
+            }
+            .try_into()?;
+            response.ensure_ok("block")?;
+            Ok((out_hash, out_data))
+        })
     }
 
     /// Get the slot time (in milliseconds) of the last finalized block.
     pub fn get_last_finalized_block_slot_time_v2(
         &self,
     ) -> concordium_base::common::types::Timestamp {
-        let consensus = self.consensus.load(Ordering::SeqCst);
-        let millis = unsafe { getLastFinalizedBlockSlotTimeV2(consensus) };
-        millis.into()
+        self.timed("get_last_finalized_block_slot_time_v2", || {
+            let consensus = self.consensus.load(Ordering::SeqCst);
+            let millis = unsafe { getLastFinalizedBlockSlotTimeV2(consensus) };
+            millis.into()
+        })
     }
 }
 
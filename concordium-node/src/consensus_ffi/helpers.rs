@@ -95,6 +95,29 @@ impl fmt::Display for PacketType {
 }
 
 impl PacketType {
+    /// Read the packet type from the 1-byte tag that the contents of every
+    /// packet start with, returning it along with the rest of the contents.
+    pub fn read_from(packet: &[u8]) -> anyhow::Result<(PacketType, &[u8])> {
+        let (&tag, content) = packet.split_first().context("Packet payload can't be empty")?;
+        Ok((PacketType::try_from(tag)?, content))
+    }
+
+    /// Read the genesis index that the contents of packets of this type start
+    /// with, returning it along with the rest of the contents. Transactions
+    /// don't have a genesis index.
+    pub fn read_genesis_index(self, content: &[u8]) -> Result<(u32, &[u8]), ShortPacket> {
+        match content.get(..4) {
+            Some(index) => {
+                let index = u32::from_be_bytes([index[0], index[1], index[2], index[3]]);
+                Ok((index, &content[4..]))
+            }
+            None => Err(ShortPacket {
+                packet_type: self,
+                len:         content.len(),
+            }),
+        }
+    }
+
     /// Determine whether a packet type can be relayed.
     /// Those that are must be subject to appropriate de-duplication
     /// checks to ensure they are not relayed endlessly.
@@ -121,6 +144,15 @@ impl PacketType {
     }
 }
 
+/// Raised when the contents of a packet are too short to contain the genesis
+/// index they should start with.
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("The contents of a {packet_type} are too short to contain a genesis index ({len} bytes)")]
+pub struct ShortPacket {
+    pub packet_type: PacketType,
+    pub len:         usize,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Error)]
 #[must_use]
 pub enum ConsensusFfiResponse {
@@ -442,3 +474,54 @@ pub enum ContractStateResponse {
         loader: concordium_smart_contract_engine::v1::trie::LoadCallback,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PacketType, ShortPacket, PACKET_TYPE_FROM_INT};
+
+    #[test]
+    fn packet_types_are_read() {
+        assert!(PacketType::read_from(&[]).is_err());
+        assert!(PacketType::read_from(&[PACKET_TYPE_FROM_INT.len() as u8, 1, 2]).is_err());
+
+        let packet = [PacketType::FinalizationRecord as u8, 1, 2];
+        let (packet_type, content) = PacketType::read_from(&packet).unwrap();
+        assert_eq!(packet_type, PacketType::FinalizationRecord);
+        assert_eq!(content, &[1, 2]);
+
+        let packet = [PacketType::CatchUpStatus as u8];
+        let (packet_type, content) = PacketType::read_from(&packet).unwrap();
+        assert_eq!(packet_type, PacketType::CatchUpStatus);
+        assert!(content.is_empty());
+    }
+
+    #[test]
+    fn genesis_indices_are_read() {
+        let content = [0, 0, 1, 2, 3];
+        let (index, rest) = PacketType::Block.read_genesis_index(&content).unwrap();
+        assert_eq!(index, 0x0102);
+        assert_eq!(rest, &[3]);
+
+        // the message declares a block, but its contents are cut short
+        let packet = [PacketType::Block as u8, 0, 0];
+        let (packet_type, content) = PacketType::read_from(&packet).unwrap();
+        assert_eq!(
+            packet_type.read_genesis_index(content),
+            Err(ShortPacket {
+                packet_type: PacketType::Block,
+                len:         2,
+            })
+        );
+    }
+
+    #[test]
+    fn packet_prefix_is_big_endian() {
+        // the packet type tag followed by the genesis index in big-endian
+        let packet = [0x03, 0x12, 0x34, 0x56, 0x78, 0xab];
+        let (packet_type, content) = PacketType::read_from(&packet).unwrap();
+        assert_eq!(packet_type, PacketType::FinalizationMessage);
+        let (index, rest) = packet_type.read_genesis_index(content).unwrap();
+        assert_eq!(index, 0x12345678);
+        assert_eq!(rest, &[0xab]);
+    }
+}
@@ -50,7 +50,7 @@ impl health_server::Health for HealthServiceImpl {
         }
 
         if let Some(min_allowed_peers) = self.health_min_peers {
-            let num_peers = self.node.get_peer_stats(Some(PeerType::Node)).len();
+            let num_peers = self.node.count_peers(Some(PeerType::Node));
             if num_peers < min_allowed_peers {
                 return Err(tonic::Status::unavailable(format!(
                     "The node only has {} peers, but is required to have at least {}.",
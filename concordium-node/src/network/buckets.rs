@@ -5,11 +5,13 @@ use rand::seq::IteratorRandom;
 use std::{
     collections::HashSet,
     hash::{Hash, Hasher},
+    net::SocketAddr,
 };
 
 use crate::{
-    common::{get_current_stamp, p2p_peer::RemotePeerId, PeerType, RemotePeer},
-    network::Networks,
+    common::{get_current_stamp, p2p_peer::RemotePeerId, P2PNodeId, PeerType, RemotePeer},
+    network::{NetworkId, Networks},
+    utils::{canonical_addr, peer_subnet},
 };
 
 const BUCKET_COUNT: usize = 1;
@@ -31,6 +33,20 @@ impl Hash for Node {
     fn hash<H: Hasher>(&self, state: &mut H) { self.peer.hash(state) }
 }
 
+/// A serializable description of a bucketed node, used to export and import
+/// the peer topology.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BucketEntry {
+    /// The id the node identifies itself with, as a hex string.
+    pub id:        String,
+    /// The address the node can be reached at.
+    pub addr:      SocketAddr,
+    /// The networks the node is a member of, in ascending order.
+    pub networks:  Vec<u16>,
+    /// The timestamp pointing to when the node was seen last.
+    pub last_seen: u64,
+}
+
 /// A bucket of nodes.
 pub type Bucket = HashSet<Node>;
 
@@ -48,21 +64,56 @@ impl Default for Buckets {
 }
 
 impl Buckets {
-    /// Adds a peer to a bucket.
+    /// Adds a peer to a bucket. A node with the same id and address, e.g., one
+    /// that was reached via the IPv4-mapped form of the address, is replaced.
+    /// A new node isn't added if the bucket already contains
+    /// `max_per_subnet` nodes in its subnet. Returns whether the peer is in the
+    /// bucket.
     pub fn insert_into_bucket(
         &mut self,
         peer: RemotePeer,
         networks: Networks,
+        max_per_subnet: Option<u16>,
         bucket_size_gauge: &IntGaugeVec,
-    ) {
+    ) -> bool {
+        self.insert_node(peer, networks, get_current_stamp(), max_per_subnet, bucket_size_gauge)
+    }
+
+    /// Adds a peer seen last at the given time to a bucket; see
+    /// `insert_into_bucket`.
+    fn insert_node(
+        &mut self,
+        mut peer: RemotePeer,
+        networks: Networks,
+        last_seen: u64,
+        max_per_subnet: Option<u16>,
+        bucket_size_gauge: &IntGaugeVec,
+    ) -> bool {
+        peer.addr = canonical_addr(peer.addr);
         let bucket = &mut self.buckets[0];
-        if bucket.insert(Node {
+        bucket.retain(|node| {
+            node.peer == peer
+                || peer.self_id.is_none()
+                || node.peer.self_id != peer.self_id
+                || canonical_addr(node.peer.external_addr()) != peer.external_addr()
+        });
+        if let Some(max) = max_per_subnet {
+            let subnet = peer_subnet(peer.addr.ip());
+            let in_subnet = bucket
+                .iter()
+                .filter(|node| node.peer != peer && peer_subnet(node.peer.addr.ip()) == subnet)
+                .count();
+            if in_subnet >= max as usize {
+                return false;
+            }
+        }
+        bucket.insert(Node {
             peer,
             networks,
-            last_seen: get_current_stamp(),
-        }) {
-            bucket_size_gauge.with_label_values(&["0"]).inc();
-        }
+            last_seen,
+        });
+        bucket_size_gauge.with_label_values(&["0"]).set(bucket.len() as i64);
+        true
     }
 
     /// Update the networks of a node in the bucket.
@@ -80,7 +131,7 @@ impl Buckets {
     fn get_all_nodes(&self, sender: Option<RemotePeerId>, networks: &Networks) -> Vec<RemotePeer> {
         let mut nodes = Vec::new();
         let filter_criteria = |node: &&Node| {
-            node.peer.peer_type == PeerType::Node
+            node.peer.peer_type.is_kept_in_buckets()
                 && Some(node.peer.local_id) != sender
                 && (networks.is_empty() || !node.networks.is_disjoint(networks))
         };
@@ -111,6 +162,58 @@ impl Buckets {
         self.get_all_nodes(Some(sender), networks).into_iter().choose_multiple(&mut rng, number)
     }
 
+    /// Returns a description of all the bucketed nodes, ordered by their ids.
+    pub fn export(&self) -> Vec<BucketEntry> {
+        let mut entries = self
+            .buckets
+            .iter()
+            .flat_map(HashSet::iter)
+            .filter_map(|node| {
+                let peer = node.peer.peer()?;
+                let mut networks = node.networks.iter().map(|net| net.id).collect::<Vec<_>>();
+                networks.sort_unstable();
+                Some(BucketEntry {
+                    id: peer.id.to_string(),
+                    addr: peer.addr,
+                    networks,
+                    last_seen: node.last_seen,
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        entries
+    }
+
+    /// Adds the described nodes to a bucket, preserving their last-seen
+    /// timestamps. Since the nodes are not connected, each of them is
+    /// assigned a fresh local identifier by `next_local_id`. The nodes are
+    /// added as by `insert_into_bucket`, so a node that is already in the
+    /// bucket is replaced. If any of the entries is invalid, none are added.
+    pub fn import(
+        &mut self,
+        entries: &[BucketEntry],
+        mut next_local_id: impl FnMut() -> RemotePeerId,
+        max_per_subnet: Option<u16>,
+        bucket_size_gauge: &IntGaugeVec,
+    ) -> anyhow::Result<()> {
+        let self_ids = entries
+            .iter()
+            .map(|entry| entry.id.parse::<P2PNodeId>())
+            .collect::<Result<Vec<_>, _>>()?;
+        for (entry, self_id) in entries.iter().zip(self_ids) {
+            let peer = RemotePeer {
+                self_id:       Some(self_id),
+                addr:          entry.addr,
+                local_id:      next_local_id(),
+                external_port: entry.addr.port(),
+                peer_type:     PeerType::Node,
+            };
+            let networks = entry.networks.iter().copied().map(NetworkId::from).collect();
+            self.insert_node(peer, networks, entry.last_seen, max_per_subnet, bucket_size_gauge);
+        }
+        Ok(())
+    }
+
     /// Removes the bucket nodes older than then specified amount of time.
     pub fn clean_buckets(
         &mut self,
@@ -161,8 +264,116 @@ mod tests {
             .expect("Unable to create dummy gauge.");
 
         // and check that only one peer is inserted
-        buckets.insert_into_bucket(p2p_peer, Default::default(), &dummy_gauge);
-        buckets.insert_into_bucket(p2p_duplicate_peer, Default::default(), &dummy_gauge);
+        buckets.insert_into_bucket(p2p_peer, Default::default(), None, &dummy_gauge);
+        buckets.insert_into_bucket(p2p_duplicate_peer, Default::default(), None, &dummy_gauge);
         assert_eq!(buckets.buckets.len(), 1);
     }
+
+    #[test]
+    pub fn test_buckets_insert_mapped_address() {
+        let mut buckets = Buckets::default();
+
+        // Create the same peer reached via the IPv4-mapped and the plain address.
+        let self_id = Some(rand::thread_rng().gen::<P2PNodeId>());
+        let mapped_peer = RemotePeer {
+            self_id,
+            addr: SocketAddr::new(IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped()), 8888),
+            local_id: rand::thread_rng().gen(),
+            external_port: 8888,
+            peer_type: PeerType::Node,
+        };
+        let plain_peer = RemotePeer {
+            self_id,
+            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8888),
+            local_id: rand::thread_rng().gen(),
+            external_port: 8888,
+            peer_type: PeerType::Node,
+        };
+
+        let dummy_gauge =
+            IntGaugeVec::new(prometheus::Opts::new("bucket_dummy_gauge", "help"), &["bucket"])
+                .expect("Unable to create dummy gauge.");
+
+        buckets.insert_into_bucket(mapped_peer, Default::default(), None, &dummy_gauge);
+        buckets.insert_into_bucket(plain_peer, Default::default(), None, &dummy_gauge);
+        assert_eq!(buckets.buckets[0].len(), 1);
+        assert_eq!(dummy_gauge.with_label_values(&["0"]).get(), 1);
+        let node = buckets.buckets[0].iter().next().unwrap();
+        assert_eq!(node.peer.local_id, plain_peer.local_id);
+        assert_eq!(node.peer.addr, plain_peer.addr);
+    }
+
+    #[test]
+    pub fn test_buckets_limit_peers_per_subnet() {
+        let mut buckets = Buckets::default();
+
+        let peer_at = |ip: [u8; 4]| RemotePeer {
+            self_id:       Some(rand::thread_rng().gen::<P2PNodeId>()),
+            addr:          SocketAddr::new(IpAddr::from(ip), 8888),
+            local_id:      rand::thread_rng().gen(),
+            external_port: 8888,
+            peer_type:     PeerType::Node,
+        };
+        let first = peer_at([10, 1, 2, 1]);
+        let second = peer_at([10, 1, 2, 2]);
+
+        let dummy_gauge =
+            IntGaugeVec::new(prometheus::Opts::new("bucket_dummy_gauge", "help"), &["bucket"])
+                .expect("Unable to create dummy gauge.");
+        let mut insert =
+            |peer| buckets.insert_into_bucket(peer, Default::default(), Some(2), &dummy_gauge);
+
+        assert!(insert(first));
+        assert!(insert(second));
+        // the subnet is full
+        assert!(!insert(peer_at([10, 1, 2, 3])));
+        // but nodes already in the bucket can still be updated
+        assert!(insert(first));
+        // and other subnets are unaffected
+        assert!(insert(peer_at([10, 1, 3, 1])));
+        assert_eq!(buckets.buckets[0].len(), 3);
+    }
+
+    #[test]
+    pub fn test_buckets_import() {
+        let mut buckets = Buckets::default();
+        let mut local_ids = 0usize;
+        let mut next_local_id = || {
+            local_ids += 1;
+            RemotePeerId::from(local_ids)
+        };
+        let dummy_gauge =
+            IntGaugeVec::new(prometheus::Opts::new("bucket_dummy_gauge", "help"), &["bucket"])
+                .expect("Unable to create dummy gauge.");
+
+        let entry = |id: P2PNodeId, addr: &str| BucketEntry {
+            id:        id.to_string(),
+            addr:      addr.parse().unwrap(),
+            networks:  vec![100],
+            last_seen: 1,
+        };
+        let entries = vec![
+            entry(rand::thread_rng().gen(), "10.1.2.1:8888"),
+            entry(rand::thread_rng().gen(), "10.1.2.2:8888"),
+            entry(rand::thread_rng().gen(), "10.1.2.3:8888"),
+        ];
+
+        // an invalid entry prevents the whole import
+        let mut invalid = entries.clone();
+        invalid[2].id = "not an id".to_owned();
+        assert!(buckets.import(&invalid, &mut next_local_id, None, &dummy_gauge).is_err());
+        assert!(buckets.buckets[0].is_empty());
+
+        // importing the same entries twice doesn't duplicate them
+        buckets.import(&entries, &mut next_local_id, None, &dummy_gauge).unwrap();
+        buckets.import(&entries, &mut next_local_id, None, &dummy_gauge).unwrap();
+        assert_eq!(buckets.buckets[0].len(), 3);
+        assert_eq!(dummy_gauge.with_label_values(&["0"]).get(), 3);
+        assert!(buckets.buckets[0].iter().all(|node| node.last_seen == 1));
+
+        // and the limit on the number of peers per subnet applies
+        let mut buckets = Buckets::default();
+        buckets.import(&entries, &mut next_local_id, Some(2), &dummy_gauge).unwrap();
+        assert_eq!(buckets.buckets[0].len(), 2);
+    }
 }
@@ -8,6 +8,7 @@ use crate::common::{
     p2p_peer::{P2PPeer, RemotePeerId},
     P2PNodeId,
 };
+use anyhow::ensure;
 use concordium_base::hashes::BlockHash;
 use nohash_hasher::BuildNoHashHasher;
 use semver::Version;
@@ -41,6 +42,28 @@ impl From<u16> for NetworkId {
     }
 }
 
+impl NetworkId {
+    /// The largest valid network id; `65535` is reserved.
+    pub const MAX: u16 = u16::MAX - 1;
+    /// The smallest valid network id; `0` is reserved.
+    pub const MIN: u16 = 1;
+
+    /// Create a network id, checking that it is in the valid range, i.e. that
+    /// it is none of the reserved ids. This is meant for ids supplied by the
+    /// user; `from` is used for the ones that are already known to be valid
+    /// or that come from peers.
+    pub fn try_new(id: u16) -> anyhow::Result<Self> {
+        ensure!(
+            (Self::MIN..=Self::MAX).contains(&id),
+            "Invalid network id {}: network ids must be between {} and {}",
+            id,
+            Self::MIN,
+            Self::MAX
+        );
+        Ok(NetworkId::from(id))
+    }
+}
+
 /// The collection of networks a node belongs to.
 pub type Networks = HashSet<NetworkId, BuildNoHashHasher<u16>>;
 
@@ -55,6 +78,27 @@ pub struct NetworkMessage {
     pub payload:  NetworkPayload,
 }
 
+impl NetworkMessage {
+    /// The time in milliseconds between the creation of a received message by
+    /// the peer and its receipt, which includes any difference between the
+    /// peers' clocks.
+    pub fn transit_time(&self) -> Option<u64> {
+        self.received.map(|received| received.saturating_sub(self.created))
+    }
+
+    /// Check whether the message is a broadcast packet that took longer than
+    /// `max_age` milliseconds to arrive.
+    pub fn is_stale_broadcast(&self, max_age: u64) -> bool {
+        match self.payload {
+            NetworkPayload::NetworkPacket(NetworkPacket {
+                destination: PacketDestination::Broadcast(..),
+                ..
+            }) => self.transit_time().map_or(false, |transit_time| transit_time > max_age),
+            _ => false,
+        }
+    }
+}
+
 /// A helper macro used to create a network message with the given payload.
 #[macro_export]
 macro_rules! netmsg {
@@ -127,3 +171,28 @@ pub enum PacketDestination {
     /// All peers, optionally excluding the ones in the vector.
     Broadcast(Vec<RemotePeerId>),
 }
+
+impl PacketDestination {
+    /// The kind of the destination, as used to label the packet metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PacketDestination::Direct(..) => "direct",
+            PacketDestination::Broadcast(..) => "broadcast",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkId;
+
+    #[test]
+    fn network_ids_are_validated() {
+        for id in [NetworkId::MIN, 100, 1000, NetworkId::MAX] {
+            assert_eq!(NetworkId::try_new(id).unwrap(), NetworkId::from(id));
+        }
+        for id in [0, u16::MAX] {
+            assert!(NetworkId::try_new(id).is_err());
+        }
+    }
+}
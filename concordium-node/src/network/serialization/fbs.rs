@@ -61,7 +61,7 @@ impl NetworkMessage {
 
         let message_offset =
             network::NetworkMessage::create(&mut builder, &network::NetworkMessageArgs {
-                timestamp: get_current_stamp(),
+                timestamp: self.created,
                 payload_type,
                 payload: Some(payload_offset),
             });
@@ -2,8 +2,10 @@ use semver::Version;
 
 use crate::{
     common::{get_current_stamp, p2p_peer::P2PPeer, P2PNodeId, PeerType},
+    consensus_ffi::helpers::PacketType,
     network::{
-        Handshake, NetworkId, NetworkMessage, NetworkPayload, NetworkRequest, NetworkResponse,
+        Handshake, NetworkId, NetworkMessage, NetworkPacket, NetworkPayload, NetworkRequest,
+        NetworkResponse, PacketDestination,
     },
     test_utils::{create_random_packet, dummy_regenesis_blocks},
 };
@@ -90,9 +92,78 @@ fn s11n_packet() {
     assert_eq!(deserialized.payload, msg.payload);
 }
 
+// The messages are flatbuffers, whose scalars are little-endian, but the
+// packet payload is passed through verbatim, so its tag and big-endian genesis
+// index are kept.
+#[test]
+fn s11n_packet_layout() {
+    let msg = NetworkMessage {
+        created:  0x0102_0304_0506_0708,
+        received: None,
+        payload:  NetworkPayload::NetworkPacket(NetworkPacket {
+            destination: PacketDestination::Broadcast(vec![]),
+            network_id:  NetworkId::from(0x1234),
+            message:     vec![PacketType::Block as u8, 0x00, 0x00, 0x00, 0x07, 0xab, 0xcd],
+        }),
+    };
+    let mut buffer = Vec::new();
+    msg.serialize(&mut buffer).unwrap();
+
+    let contains = |bytes: &[u8]| buffer.windows(bytes.len()).any(|window| window == bytes);
+    // the size prefix and the protocol identifier
+    assert_eq!(buffer[..4], ((buffer.len() - 4) as u32).to_le_bytes());
+    assert_eq!(&buffer[8..12], b"CP2P");
+    // the timestamp and the network id
+    assert!(contains(&[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]));
+    assert!(contains(&[0x34, 0x12]));
+    // the length of the payload, followed by the packet type tag, the genesis
+    // index and the rest of the payload
+    assert!(contains(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0xab, 0xcd]));
+}
+
 quickcheck! {
     fn s11n_fuzzed(bytes: Vec<u8>) -> bool {
         let _ = NetworkMessage::deserialize(&bytes);
         true
     }
 }
+
+#[test]
+fn s11n_preserves_timestamps() {
+    let msg = NetworkMessage {
+        created:  get_current_stamp() - 250,
+        received: None,
+        payload:  NetworkPayload::NetworkRequest(NetworkRequest::Ping),
+    };
+    let mut buffer = Cursor::new(Vec::new());
+
+    msg.serialize(&mut buffer).unwrap();
+    let deserialized = NetworkMessage::deserialize(&buffer.get_ref()).unwrap();
+    assert_eq!(deserialized.created, msg.created);
+    assert!(deserialized.received.unwrap() >= msg.created + 250);
+    assert!(deserialized.transit_time().unwrap() >= 250);
+    assert_eq!(msg.transit_time(), None);
+}
+
+#[test]
+fn old_broadcasts_are_stale() {
+    let received = |destination, age| {
+        let msg = NetworkMessage {
+            created:  get_current_stamp() - age,
+            received: None,
+            payload:  NetworkPayload::NetworkPacket(NetworkPacket {
+                destination,
+                network_id: NetworkId::from(100),
+                message: vec![0],
+            }),
+        };
+        let mut buffer = Cursor::new(Vec::new());
+        msg.serialize(&mut buffer).unwrap();
+        NetworkMessage::deserialize(&buffer.get_ref()).unwrap()
+    };
+
+    assert!(received(PacketDestination::Broadcast(vec![]), 10_000).is_stale_broadcast(5_000));
+    assert!(!received(PacketDestination::Broadcast(vec![]), 0).is_stale_broadcast(5_000));
+    // direct packets, e.g. catch-up responses, are never stale
+    assert!(!received(PacketDestination::Direct(1usize.into()), 10_000).is_stale_broadcast(5_000));
+}
@@ -0,0 +1,246 @@
+//! Bootstrapper quality tracking.
+
+use crate::{lock_or_die, p2p::P2PNode};
+use anyhow::bail;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use concordium_base::common::{Buffer, Deserial, Serial};
+use rkv::{StoreOptions, Value};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+const BOOTSTRAPPER_STORE_NAME: &str = "bootstrappers";
+
+/// The maximum average number of peers per handshake that counts towards the
+/// score of a bootstrapper, so that returning large peer lists doesn't outweigh
+/// being reachable.
+const MAX_SCORED_PEERS_PER_HANDSHAKE: u64 = 10;
+
+/// The persisted track record of a bootstrapper.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BootstrapperRecord {
+    /// The number of completed handshakes.
+    pub handshakes:     u64,
+    /// The total number of peers the bootstrapper returned.
+    pub peers_returned: u64,
+    /// The number of bootstrap rounds in which no handshake was completed.
+    pub failures:       u64,
+}
+
+impl BootstrapperRecord {
+    /// The score by which bootstrappers are ranked; a higher score is better.
+    /// The returned peers count by their average number per handshake, up to
+    /// `MAX_SCORED_PEERS_PER_HANDSHAKE`.
+    pub fn score(&self) -> i64 {
+        let peers_per_handshake =
+            (self.peers_returned / self.handshakes.max(1)).min(MAX_SCORED_PEERS_PER_HANDSHAKE);
+        self.handshakes as i64 + peers_per_handshake as i64 - self.failures as i64
+    }
+
+    fn add(&mut self, other: &BootstrapperRecord) {
+        self.handshakes += other.handshakes;
+        self.peers_returned += other.peers_returned;
+        self.failures += other.failures;
+    }
+}
+
+impl Serial for BootstrapperRecord {
+    fn serial<W: Buffer + WriteBytesExt>(&self, target: &mut W) {
+        self.handshakes.serial(target);
+        self.peers_returned.serial(target);
+        self.failures.serial(target);
+    }
+}
+
+impl Deserial for BootstrapperRecord {
+    fn deserial<R: ReadBytesExt>(source: &mut R) -> anyhow::Result<Self> {
+        Ok(BootstrapperRecord {
+            handshakes:     u64::deserial(source)?,
+            peers_returned: u64::deserial(source)?,
+            failures:       u64::deserial(source)?,
+        })
+    }
+}
+
+fn store_key(addr: SocketAddr) -> Vec<u8> {
+    let mut key = Vec::new();
+    addr.ip().serial(&mut key);
+    addr.port().serial(&mut key);
+    key
+}
+
+fn read_store_key(mut key: &[u8]) -> anyhow::Result<SocketAddr> {
+    let ip = IpAddr::deserial(&mut key)?;
+    let port = u16::deserial(&mut key)?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+impl P2PNode {
+    /// Update the record of the given bootstrapper. The update is kept in
+    /// memory until the records are persisted by
+    /// `flush_bootstrapper_records`.
+    fn update_bootstrapper_record(
+        &self,
+        addr: SocketAddr,
+        f: impl FnOnce(&mut BootstrapperRecord),
+    ) {
+        f(lock_or_die!(self.connection_handler.bootstrapper_updates).entry(addr).or_default());
+    }
+
+    /// Persist the updates of the bootstrapper records made since the last
+    /// flush in a single transaction. This is done during housekeeping, so
+    /// that the updates don't each write to the store from the poll loop.
+    pub fn flush_bootstrapper_records(&self) {
+        let updates =
+            std::mem::take(&mut *lock_or_die!(self.connection_handler.bootstrapper_updates));
+        if updates.is_empty() {
+            return;
+        }
+        if let Err(e) = self.persist_bootstrapper_updates(&updates) {
+            error!("Can't persist the bootstrapper records: {}", e);
+        }
+    }
+
+    fn persist_bootstrapper_updates(
+        &self,
+        updates: &HashMap<SocketAddr, BootstrapperRecord>,
+    ) -> anyhow::Result<()> {
+        if let Ok(kvs_env) = self.kvs.read() {
+            let store = kvs_env.open_single(BOOTSTRAPPER_STORE_NAME, StoreOptions::create())?;
+            let mut writer = kvs_env.write()?;
+            for (&addr, update) in updates {
+                let key = store_key(addr);
+                let mut record = match store.get(&writer, &key)? {
+                    Some(Value::Blob(mut bytes)) => BootstrapperRecord::deserial(&mut bytes)?,
+                    _ => BootstrapperRecord::default(),
+                };
+                record.add(update);
+                let mut value = Vec::new();
+                record.serial(&mut value);
+                store.put(&mut writer, key, &Value::Blob(&value))?;
+            }
+            writer.commit()?;
+            Ok(())
+        } else {
+            bail!("Couldn't update the bootstrapper records: couldn't obtain a lock over the kvs");
+        }
+    }
+
+    /// Register the bootstrappers contacted in a new bootstrap round. Those
+    /// from the previous round that still haven't completed a handshake are
+    /// recorded as failures.
+    pub fn register_bootstrap_round(&self, addrs: &[SocketAddr]) {
+        let missing = {
+            let mut pending = lock_or_die!(self.connection_handler.pending_bootstraps);
+            let missing = pending.drain().collect::<Vec<_>>();
+            pending.extend(addrs.iter().copied());
+            missing
+        };
+        for addr in missing {
+            self.update_bootstrapper_record(addr, |record| record.failures += 1);
+        }
+    }
+
+    /// Record a completed handshake with the bootstrapper at the given address.
+    pub fn register_bootstrapper_handshake(&self, addr: SocketAddr) {
+        lock_or_die!(self.connection_handler.pending_bootstraps).remove(&addr);
+        self.update_bootstrapper_record(addr, |record| record.handshakes += 1);
+    }
+
+    /// Record the number of peers returned by the bootstrapper at the given
+    /// address.
+    pub fn register_bootstrapper_peers(&self, addr: SocketAddr, count: usize) {
+        self.update_bootstrapper_record(addr, |record| record.peers_returned += count as u64);
+    }
+
+    /// Obtain the records of all the known bootstrappers, best first. The
+    /// records include the updates that haven't been persisted yet.
+    pub fn get_bootstrapper_ranking(
+        &self,
+    ) -> anyhow::Result<Vec<(SocketAddr, BootstrapperRecord)>> {
+        if let Ok(kvs_env) = self.kvs.read() {
+            let store = kvs_env.open_single(BOOTSTRAPPER_STORE_NAME, StoreOptions::create())?;
+            let reader = kvs_env.read()?;
+
+            let mut records = HashMap::new();
+            for entry in store.iter_start(&reader)? {
+                let (key, value) = entry?;
+                let addr = read_store_key(key)?;
+                let record = match value {
+                    Value::Blob(mut bytes) => BootstrapperRecord::deserial(&mut bytes)?,
+                    _ => bail!("Invalid record of bootstrapper {}", addr),
+                };
+                records.insert(addr, record);
+            }
+            for (&addr, update) in lock_or_die!(self.connection_handler.bootstrapper_updates).iter()
+            {
+                records.entry(addr).or_insert_with(BootstrapperRecord::default).add(update);
+            }
+
+            let mut ranking = records.into_iter().collect::<Vec<_>>();
+            ranking.sort_by_key(|(_, record)| -record.score());
+
+            Ok(ranking)
+        } else {
+            bail!("Couldn't get the bootstrapper ranking: couldn't obtain a lock over the kvs");
+        }
+    }
+
+    /// Order the given bootstrapper addresses so that the historically most
+    /// useful ones come first. Unknown bootstrappers are placed between the
+    /// useful and the bad ones, and otherwise the given order is kept.
+    pub fn rank_bootstrappers(&self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self.get_bootstrapper_ranking() {
+            Ok(ranking) => {
+                let scores = ranking
+                    .into_iter()
+                    .map(|(addr, record)| (addr, record.score()))
+                    .collect::<HashMap<_, _>>();
+                addrs.sort_by_key(|addr| -scores.get(addr).copied().unwrap_or(0));
+            }
+            Err(e) => error!("Can't rank the bootstrappers: {}", e),
+        }
+        addrs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootstrapper_records_are_big_endian() {
+        let record = BootstrapperRecord {
+            handshakes:     1,
+            peers_returned: 0x0102_0304,
+            failures:       0x0a0b_0c0d_0e0f_1011,
+        };
+        let golden = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // handshakes
+            0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, // peers_returned
+            0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, // failures
+        ];
+
+        let mut bytes = Vec::new();
+        record.serial(&mut bytes);
+        assert_eq!(bytes, golden);
+        assert_eq!(BootstrapperRecord::deserial(&mut &golden[..]).unwrap(), record);
+    }
+
+    #[test]
+    fn returned_peers_score_is_bounded() {
+        let reliable = BootstrapperRecord {
+            handshakes:     20,
+            peers_returned: 200,
+            failures:       0,
+        };
+        let generous = BootstrapperRecord {
+            handshakes:     1,
+            peers_returned: 10_000,
+            failures:       5,
+        };
+        assert_eq!(reliable.score(), 30);
+        assert_eq!(generous.score(), 6);
+    }
+}
@@ -4,17 +4,21 @@ use crate::{
     common::{get_current_stamp, p2p_peer::RemotePeerId, P2PNodeId, PeerType, RemotePeer},
     configuration as config,
     connection::{ConnChange, Connection, MessageSendingPriority},
+    consensus_ffi::helpers::PacketType,
     lock_or_die, netmsg,
     network::{
-        Handshake, NetworkId, NetworkPacket, NetworkRequest, PacketDestination,
+        Handshake, NetworkId, NetworkPacket, NetworkRequest, Networks, PacketDestination,
         WIRE_PROTOCOL_VERSIONS,
     },
     p2p::{
         bans::{BanId, PersistedBanId},
         maintenance::attempt_bootstrap,
-        P2PNode,
+        Connections, P2PNode,
     },
-    read_or_die, write_or_die,
+    read_or_die,
+    stats_export_service::NodeState,
+    utils::{canonical_addr, peer_subnet},
+    write_or_die,
 };
 use anyhow::bail;
 use mio::{event::Event, net::TcpStream, Events, Token};
@@ -22,9 +26,14 @@ use rand::seq::IteratorRandom;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 use semver::Version;
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     io,
     net::{IpAddr, SocketAddr},
-    sync::{atomic::Ordering, Arc},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
     time::{Duration, Instant},
 };
 use thiserror::Error;
@@ -32,28 +41,33 @@ use thiserror::Error;
 /// The poll token of the node's socket server.
 pub const SELF_TOKEN: Token = Token(0);
 
+/// The number of close causes kept by the node.
+const MAX_CLOSE_CAUSES: usize = 100;
+
 impl P2PNode {
     /// Broadcast a request to join a network.
     /// Note that this needs a write lock on the node's connections object.
     pub fn send_join_network(&self, id: NetworkId) {
-        self.broadcast_network_request(NetworkRequest::JoinNetwork(id))
+        self.broadcast_network_request(id, NetworkRequest::JoinNetwork(id))
     }
 
     /// Broadcast a request to leave the network.
     /// Note that this needs a write lock on the node's connections object.
     pub fn send_leave_network(&self, id: NetworkId) {
-        self.broadcast_network_request(NetworkRequest::LeaveNetwork(id))
+        self.broadcast_network_request(id, NetworkRequest::LeaveNetwork(id))
     }
 
-    /// Send a network change request to all the peers.
+    /// Send a request concerning the given network to all the peers of the
+    /// types the network is announced to; see `announced_networks`.
     /// Note that this needs a write lock on the node's connections object.
-    pub fn broadcast_network_request(&self, request: NetworkRequest) {
+    pub fn broadcast_network_request(&self, network: NetworkId, request: NetworkRequest) {
         let message = netmsg!(NetworkRequest, request);
         let mut serialized = Vec::with_capacity(256);
         if let Err(e) = message.serialize(&mut serialized) {
             error!("Could not serialize a network request message: {}", e)
         } else {
-            let filter = |_: &Connection| true;
+            let filter =
+                |conn: &Connection| self.is_announced_network(conn.remote_peer_type(), network);
             self.send_over_all_connections(&serialized, &filter);
         }
     }
@@ -64,13 +78,28 @@ impl P2PNode {
         &self,
         data: &[u8],
         conn_filter: &dyn Fn(&Connection) -> bool,
+    ) -> usize {
+        self.send_over_all_connections_with_priority(
+            data,
+            conn_filter,
+            MessageSendingPriority::Normal,
+        )
+    }
+
+    /// Send a `data` message with the given priority to all connections
+    /// adhering to the specified filter. Returns the number of sent messages.
+    pub fn send_over_all_connections_with_priority(
+        &self,
+        data: &[u8],
+        conn_filter: &dyn Fn(&Connection) -> bool,
+        priority: MessageSendingPriority,
     ) -> usize {
         let mut sent_messages = 0usize;
         let data = Arc::from(data);
 
         for conn in write_or_die!(self.connections()).values_mut().filter(|conn| conn_filter(conn))
         {
-            conn.async_send(Arc::clone(&data), MessageSendingPriority::Normal);
+            conn.async_send(Arc::clone(&data), priority);
             sent_messages += 1;
         }
 
@@ -178,6 +207,27 @@ impl P2PNode {
         removed_candidates || removed_peers
     }
 
+    /// Record the error that causes a connection to be closed, dropping the
+    /// oldest recorded cause if there are too many.
+    fn record_close_cause(&self, conn: &Connection, error: &anyhow::Error) {
+        let mut causes = lock_or_die!(self.connection_handler.close_causes);
+        if causes.len() >= MAX_CLOSE_CAUSES {
+            causes.pop_front();
+        }
+        causes.push_back(CloseCause {
+            addr:      conn.remote_addr(),
+            peer_id:   conn.remote_id(),
+            timestamp: get_current_stamp(),
+            error:     error.to_string(),
+        });
+    }
+
+    /// Get the causes of the most recent connections closed after an error,
+    /// oldest first.
+    pub fn recent_close_causes(&self) -> Vec<CloseCause> {
+        lock_or_die!(self.connection_handler.close_causes).iter().cloned().collect()
+    }
+
     /// Close connection to the given address, if any.
     pub fn remove_connection_to_addr(&self, addr: SocketAddr) {
         lock_or_die!(self.conn_candidates()).retain(|_, conn| conn.remote_addr() != addr);
@@ -212,6 +262,9 @@ impl P2PNode {
             None
         };
         let network_id = inner_pkt.network_id;
+        let priority = packet_priority(&inner_pkt);
+        let sent_packets =
+            self.stats.sent_network_packets.with_label_values(&[inner_pkt.destination.kind()]);
 
         let message = netmsg!(NetworkPacket, inner_pkt);
         let mut serialized = Vec::with_capacity(256);
@@ -221,22 +274,48 @@ impl P2PNode {
         if let Some(target_token) = target {
             // direct messages
             let filter = |conn: &Connection| conn.remote_peer.local_id == target_token;
-            sent += self.send_over_all_connections(&serialized, &filter);
+            sent += self.send_over_all_connections_with_priority(&serialized, &filter, priority);
         } else {
-            // broadcast messages
-            let filter =
-                |conn: &Connection| is_valid_broadcast_target(conn, &peers_to_skip, network_id);
-            sent += self.send_over_all_connections(&serialized, &filter);
+            // broadcast messages; the valid targets whose queues are saturated are
+            // skipped
+            let is_target = |conn: &Connection| {
+                is_valid_broadcast_target(conn, &peers_to_skip, network_id)
+                    && match self.config.broadcast_high_water_mark {
+                        Some(mark) if conn.is_saturated(mark) => {
+                            self.stats.broadcast_deliveries_skipped.inc();
+                            false
+                        }
+                        _ => true,
+                    }
+            };
+            if let Some(max_peers) = self.config.broadcast_max_peers {
+                // limit the fan-out to a random selection of the targets; the
+                // recipients relay the packet further and deduplication prevents loops
+                let selected = read_or_die!(self.connections())
+                    .values()
+                    .filter(|conn| is_target(conn))
+                    .map(|conn| conn.remote_peer.local_id)
+                    .choose_multiple(&mut rand::thread_rng(), max_peers as usize);
+                let filter = |conn: &Connection| selected.contains(&conn.remote_peer.local_id);
+                sent +=
+                    self.send_over_all_connections_with_priority(&serialized, &filter, priority);
+            } else {
+                sent +=
+                    self.send_over_all_connections_with_priority(&serialized, &is_target, priority);
+            }
         }
+        sent_packets.inc_by(sent as u64);
 
         Ok(sent)
     }
 
     /// Send queued messages to and then receive any pending messages from all
-    /// the node's connections in parallel.
+    /// the node's connections in parallel. Returns whether any of the
+    /// connections still has data to be read after exhausting its read budget.
     #[inline]
-    pub fn process_network_events(&self, events: &Events) {
+    pub fn process_network_events(&self, events: &Events) -> bool {
         let conn_stats = self.get_peer_stats(Some(PeerType::Node));
+        let unread_data = AtomicBool::new(false);
 
         lock_or_die!(self.conn_candidates())
             .par_iter_mut()
@@ -251,6 +330,7 @@ impl P2PNode {
                     conn.send_pending_messages().and_then(|_| conn.low_level.flush_socket())
                 {
                     error!("[sending to {}] {}", conn, e);
+                    self.record_close_cause(conn, &e);
                     if let Ok(_io_err) = e.downcast::<io::Error>() {
                         self.register_conn_change(ConnChange::RemovalByToken(conn.token()));
                     } else {
@@ -259,10 +339,15 @@ impl P2PNode {
                     return;
                 }
 
-                if events.iter().any(|event| event.token() == conn.token() && event.is_readable()) {
+                if conn.has_unread_data()
+                    || events
+                        .iter()
+                        .any(|event| event.token() == conn.token() && event.is_readable())
+                {
                     match conn.read_stream(&conn_stats) {
                         Err(e) => {
                             error!("[receiving from {}] {}", conn, e);
+                            self.record_close_cause(conn, &e);
                             if let Ok(_io_err) = e.downcast::<io::Error>() {
                                 self.register_conn_change(ConnChange::RemovalByToken(conn.token()));
                             } else {
@@ -278,7 +363,11 @@ impl P2PNode {
                             self.register_conn_change(ConnChange::RemovalByToken(conn.token()));
                             return;
                         }
-                        Ok(true) => {}
+                        Ok(true) => {
+                            if conn.has_unread_data() {
+                                unread_data.store(true, Ordering::Relaxed);
+                            }
+                        }
                     }
                 }
 
@@ -295,17 +384,45 @@ impl P2PNode {
                     debug!("Closing connection to {}", conn);
                     self.register_conn_change(ConnChange::RemovalByToken(conn.token()));
                 }
-            })
+            });
+
+        unread_data.into_inner()
+    }
+
+    /// The networks announced to peers of the given type, i.e. those of the
+    /// node's networks that are allowed for the peer type.
+    pub fn announced_networks(&self, peer_type: PeerType) -> Networks {
+        read_or_die!(self.networks())
+            .iter()
+            .copied()
+            .filter(|&network| self.is_announced_network(peer_type, network))
+            .collect()
+    }
+
+    /// Check whether the given network may be announced to peers of the given
+    /// type.
+    pub fn is_announced_network(&self, peer_type: PeerType, network: NetworkId) -> bool {
+        let allowed = match peer_type {
+            PeerType::Node => &self.config.node_handshake_networks,
+            PeerType::Bootstrapper => &self.config.bootstrapper_handshake_networks,
+        };
+        allowed.as_ref().map_or(true, |allowed| allowed.contains(&network))
     }
 
-    /// Creates a "high-level" handshake request to be sent to new peers.
-    pub fn produce_handshake_request(&self) -> anyhow::Result<Vec<u8>> {
+    /// Creates a "high-level" handshake request to be sent to a new peer of
+    /// the given type. An outbound-only node advertises port 0, so that its
+    /// peers don't pass it on to others.
+    pub fn produce_handshake_request(&self, peer_type: PeerType) -> anyhow::Result<Vec<u8>> {
         let handshake_request = netmsg!(
             NetworkRequest,
             NetworkRequest::Handshake(Handshake {
                 remote_id:      self.self_peer.id,
-                remote_port:    self.self_peer.port(),
-                networks:       read_or_die!(self.networks()).iter().copied().collect(),
+                remote_port:    if self.config.outbound_only {
+                    0
+                } else {
+                    self.self_peer.port()
+                },
+                networks:       self.announced_networks(peer_type),
                 node_version:   Version::parse(env!("CARGO_PKG_VERSION"))?,
                 wire_versions:  WIRE_PROTOCOL_VERSIONS.to_vec(),
                 genesis_blocks: read_or_die!(self.config.regenesis_arc.blocks).clone(),
@@ -327,6 +444,33 @@ impl P2PNode {
     pub fn stop_network(&self) {
         self.config.regenesis_arc.stop_network.store(true, Ordering::Release);
     }
+
+    /// Start draining the node's connections: from now on no new connections
+    /// are accepted or made and nothing is broadcast, while the existing
+    /// connections are kept until they drop on their own.
+    pub fn begin_draining(&self) {
+        info!("Draining the connections");
+        self.connection_handler.draining.store(true, Ordering::Release);
+        self.stats.draining.set(1);
+        self.stats.advance_node_state(NodeState::Draining);
+    }
+
+    /// Check whether the node is draining its connections.
+    pub fn is_draining(&self) -> bool { self.connection_handler.draining.load(Ordering::Acquire) }
+
+    /// Wait until the node has at most `threshold` connections or `deadline`
+    /// passes, returning whether the connections were drained in time.
+    pub fn await_drained(&self, threshold: usize, deadline: Instant) -> bool {
+        loop {
+            if read_or_die!(self.connections()).len() <= threshold {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -343,10 +487,18 @@ pub enum AcceptFailureReason {
     DuplicateConnection {
         addr: SocketAddr,
     },
+    #[error("Too many peers in the subnet of {addr}. Not accepting an additional one.")]
+    SubnetFull {
+        addr: SocketAddr,
+    },
     #[error("Connection attempt from a banned address.")]
     Banned,
     #[error("Connection attempt from a soft-banned address.")]
     SoftBanned,
+    #[error("Connection attempt from {addr} while draining the connections.")]
+    Draining {
+        addr: SocketAddr,
+    },
     #[error("{err}")]
     Other {
         #[from]
@@ -354,6 +506,18 @@ pub enum AcceptFailureReason {
     },
 }
 
+/// Count the connections to nodes (i.e., not bootstrappers) in the subnet of
+/// the given address.
+fn peers_in_subnet<'a>(conns: impl Iterator<Item = &'a Connection>, addr: SocketAddr) -> usize {
+    let subnet = peer_subnet(addr.ip());
+    conns
+        .filter(|conn| {
+            conn.remote_peer_type() == PeerType::Node
+                && peer_subnet(conn.remote_addr().ip()) == subnet
+        })
+        .count()
+}
+
 /// Attempt to accept an incoming network connection.
 /// - If an error occurs, e.g., fail to accept the socket connection, or fail to
 ///   register with the poll registry return Err
@@ -365,6 +529,13 @@ pub fn accept(
     addr: SocketAddr,
 ) -> Result<Token, AcceptFailureReason> {
     node.stats.connections_received.inc();
+    let addr = canonical_addr(addr);
+
+    if node.is_draining() {
+        return Err(AcceptFailureReason::Draining {
+            addr,
+        });
+    }
 
     // if we fail to read the database we allow the connection.
     // This is fine as long as we assume that nobody can corrupt our ban database.
@@ -386,7 +557,7 @@ pub fn accept(
         // outgoing socket. We could check that it is coming from a given IP,
         // but at the moment that is not how we identify trusted addresses, so
         // it would violate the general rule and complicate local testing.
-        if node.self_peer.peer_type == PeerType::Node
+        if node.self_peer.peer_type.enforces_hard_connection_limit()
             && candidates_lock.len() + conn_read_lock.len()
                 >= node.config.hard_connection_limit as usize
         {
@@ -415,6 +586,17 @@ pub fn accept(
             warn!("Connection attempt from a soft-banned IP ({}); rejecting", addr.ip());
             return Err(AcceptFailureReason::SoftBanned);
         }
+
+        // Bootstrappers don't initiate connections, so all the incoming ones are nodes.
+        if let Some(max) = node.config.max_peers_per_subnet {
+            if peers_in_subnet(candidates_lock.values().chain(conn_read_lock.values()), addr)
+                >= max as usize
+            {
+                return Err(AcceptFailureReason::SubnetFull {
+                    addr,
+                });
+            }
+        }
     }
 
     debug!("Accepting a connection from {}", addr);
@@ -445,6 +627,7 @@ pub fn connect(
     peer_id: Option<P2PNodeId>, // id of the peer we are connecting to, if known
     respect_max_peers: bool,    // whether this should respect the maximum peers setting or not.
 ) -> anyhow::Result<()> {
+    let peer_addr = canonical_addr(peer_addr);
     debug!(
         "Attempting to connect to {}{}",
         peer_addr,
@@ -455,8 +638,12 @@ pub fn connect(
         }
     );
 
+    if node.is_draining() {
+        bail!("Refusing to connect to {} while draining the connections", peer_addr);
+    }
+
     if respect_max_peers && peer_type == PeerType::Node {
-        let current_peer_count = node.get_peer_stats(Some(PeerType::Node)).len() as u16;
+        let current_peer_count = node.count_peers(Some(PeerType::Node)) as u16;
         if current_peer_count >= node.config.max_allowed_nodes {
             bail!(
                 "Maximum number of peers reached {}/{}",
@@ -495,6 +682,41 @@ pub fn connect(
         }
     }
 
+    // Don't let a single subnet dominate the peers. Like the maximum number of
+    // peers this doesn't apply to bootstrappers and given addresses.
+    if respect_max_peers && peer_type == PeerType::Node {
+        if let Some(max) = node.config.max_peers_per_subnet {
+            let in_subnet = peers_in_subnet(
+                read_or_die!(node.connections()).values().chain(candidates_lock.values()),
+                peer_addr,
+            );
+            if in_subnet >= max as usize {
+                bail!(
+                    "Already connected to {} peers in the subnet of {}",
+                    in_subnet,
+                    peer_addr.ip()
+                );
+            }
+        }
+    }
+
+    // Don't have too many outbound connections awaiting the handshake at once; the
+    // attempt is retried from the poll loop once some of them have concluded.
+    if pending_outbound_connections(&candidates_lock)
+        >= node.config.max_pending_outbound_connections as usize
+    {
+        let attempt = DeferredConnect {
+            peer_type,
+            addr: peer_addr,
+            peer_id,
+            respect_max_peers,
+        };
+        if lock_or_die!(node.connection_handler.deferred_connects).push(attempt, Instant::now()) {
+            debug!("Too many pending outbound connections; delaying connecting to {}", peer_addr);
+        }
+        return Ok(());
+    }
+
     match TcpStream::connect(peer_addr) {
         Ok(socket) => {
             trace!("Connected to {}", peer_addr);
@@ -534,6 +756,154 @@ pub fn connect(
     }
 }
 
+/// Limits the rate of outgoing broadcasts in each network using a token bucket
+/// that allows bursts of up to a second's worth of broadcasts.
+pub struct BroadcastLimiter {
+    /// The number of broadcasts allowed per second.
+    rate:    u32,
+    /// The available tokens and the time they were last replenished.
+    buckets: HashMap<NetworkId, (f64, Instant)>,
+}
+
+impl BroadcastLimiter {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Check whether a broadcast in the given network is within the limit,
+    /// consuming one token if it is.
+    pub fn allow(&mut self, network_id: NetworkId, now: Instant) -> bool {
+        let rate = f64::from(self.rate);
+        let (tokens, last) = self.buckets.entry(network_id).or_insert((rate, now));
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * rate).min(rate);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The error that caused a connection to be closed.
+#[derive(Debug, Clone)]
+pub struct CloseCause {
+    /// The address of the peer.
+    pub addr:      SocketAddr,
+    /// The id of the peer, if the handshake had been completed.
+    pub peer_id:   Option<P2PNodeId>,
+    /// The time the error was encountered.
+    pub timestamp: u64,
+    pub error:     String,
+}
+
+/// A connection attempt that was delayed because the limit on pending outbound
+/// connections had been reached.
+pub struct DeferredConnect {
+    pub peer_type:         PeerType,
+    pub addr:              SocketAddr,
+    pub peer_id:           Option<P2PNodeId>,
+    pub respect_max_peers: bool,
+}
+
+/// The queue of deferred connection attempts, holding at most one attempt per
+/// address.
+pub struct DeferredConnects {
+    /// The maximum number of queued attempts.
+    capacity: usize,
+    /// The attempts along with the time they were deferred, oldest first.
+    queue:    VecDeque<(Instant, DeferredConnect)>,
+    /// The addresses of the queued attempts.
+    addrs:    HashSet<SocketAddr>,
+}
+
+impl DeferredConnects {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queue: VecDeque::new(),
+            addrs: HashSet::new(),
+        }
+    }
+
+    /// Queue an attempt unless one to the same address is already queued or
+    /// the queue is full. Return whether it was queued.
+    pub fn push(&mut self, attempt: DeferredConnect, now: Instant) -> bool {
+        if self.queue.len() >= self.capacity || !self.addrs.insert(attempt.addr) {
+            return false;
+        }
+        self.queue.push_back((now, attempt));
+        true
+    }
+
+    /// Take the oldest queued attempt.
+    pub fn pop(&mut self) -> Option<DeferredConnect> {
+        let (_, attempt) = self.queue.pop_front()?;
+        self.addrs.remove(&attempt.addr);
+        Some(attempt)
+    }
+
+    /// Drop the attempts that were deferred more than `max_age` ago.
+    pub fn expire(&mut self, now: Instant, max_age: Duration) {
+        while let Some((deferred_at, _)) = self.queue.front() {
+            if now.saturating_duration_since(*deferred_at) <= max_age {
+                break;
+            }
+            if let Some(attempt) = self.pop() {
+                debug!("Giving up on connecting to the delayed address {}", attempt.addr);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize { self.queue.len() }
+
+    pub fn is_empty(&self) -> bool { self.queue.is_empty() }
+}
+
+/// The number of connection candidates that we initiated, i.e. outbound
+/// connections that have not completed the handshake yet.
+fn pending_outbound_connections(candidates: &Connections) -> usize {
+    candidates.values().filter(|conn| conn.low_level.is_initiator()).count()
+}
+
+/// Retry the deferred connection attempts for as long as the number of pending
+/// outbound connections stays below the limit. Attempts that have been waiting
+/// for longer than a housekeeping interval are dropped.
+pub fn process_deferred_connects(node: &Arc<P2PNode>) {
+    let limit = node.config.max_pending_outbound_connections as usize;
+    let queued = {
+        let mut deferred = lock_or_die!(node.connection_handler.deferred_connects);
+        deferred.expire(Instant::now(), Duration::from_secs(node.config.housekeeping_interval));
+        deferred.len()
+    };
+    // bound the number of attempts, as each of them can be deferred again
+    for _ in 0..queued {
+        if pending_outbound_connections(&lock_or_die!(node.conn_candidates())) >= limit {
+            return;
+        }
+        let attempt =
+            if let Some(attempt) = lock_or_die!(node.connection_handler.deferred_connects).pop() {
+                attempt
+            } else {
+                return;
+            };
+        if let Err(e) = connect(
+            node,
+            attempt.peer_type,
+            attempt.addr,
+            attempt.peer_id,
+            attempt.respect_max_peers,
+        ) {
+            debug!("Can't connect to the delayed address {}: {}", attempt.addr, e);
+        }
+    }
+}
+
 /// Perform a round of connection maintenance, e.g. removing inactive ones.
 /// Return whether we attempted to bootstrap.
 pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
@@ -550,11 +920,14 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
         }
     };
 
+    // a peer that doesn't keep track of when its connections were last seen
+    // drops them after a fixed time instead
     let is_conn_inactive = |conn: &Connection| -> bool {
-        (peer_type == PeerType::Node
-            && conn.last_seen() + node.config.max_normal_keep_alive_ms < curr_stamp)
-            || (peer_type == PeerType::Bootstrapper
-                && conn.stats.created + config::MAX_BOOTSTRAPPER_KEEP_ALIVE < curr_stamp)
+        if peer_type.monitors_connections() {
+            conn.last_seen() + node.config.max_normal_keep_alive_ms < curr_stamp
+        } else {
+            conn.stats.created + config::MAX_BOOTSTRAPPER_KEEP_ALIVE < curr_stamp
+        }
     };
 
     let is_conn_without_handshake = |conn: &Connection| -> bool {
@@ -582,9 +955,9 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
 
     // if the number of peers exceeds the desired value, close a random selection of
     // post-handshake non-given connections to lower it
-    if peer_type == PeerType::Node {
+    if peer_type.maintains_peer_count() {
         let max_allowed_nodes = node.config.max_allowed_nodes;
-        let peer_count = node.get_peer_stats(Some(PeerType::Node)).len() as u16;
+        let peer_count = node.count_peers(Some(PeerType::Node)) as u16;
         if peer_count > max_allowed_nodes {
             // drop connections to any non-given peers.
             let mut rng = rand::thread_rng();
@@ -632,12 +1005,14 @@ pub fn connection_housekeeping(node: &Arc<P2PNode>) -> bool {
         warn!("Dropped {} low priority messages from peer {}.", dropped, peer_id);
     }
 
+    node.flush_bootstrapper_records();
+
     // Reconnect to bootstrappers after a specified amount of time.
     // It's unclear whether we should always be doing this, even if we have enough
     // peers. But the current logic is to try to bootstrap again, and if we have
     // too many peers drop a subset of them.
     if !node.config.no_bootstrap_dns
-        && peer_type == PeerType::Node
+        && peer_type.maintains_peer_count()
         && curr_stamp >= node.get_last_bootstrap() + node.config.bootstrapping_interval * 1000
     {
         attempt_bootstrap(node);
@@ -654,7 +1029,7 @@ fn is_valid_broadcast_target(
     peers_to_skip: &[RemotePeerId],
     network_id: NetworkId,
 ) -> bool {
-    conn.remote_peer.peer_type != PeerType::Bootstrapper
+    conn.remote_peer.peer_type.receives_broadcasts()
         && !peers_to_skip.contains(&conn.remote_peer.local_id)
         && conn.remote_end_networks.contains(&network_id)
 }
@@ -678,9 +1053,51 @@ pub fn send_broadcast_message(
     network_id: NetworkId,
     msg: Arc<[u8]>,
 ) -> usize {
+    if node.is_draining() {
+        return 0;
+    }
     send_message_over_network(node, None, dont_relay_to, network_id, msg)
 }
 
+/// Check whether the node may broadcast a message of the given type produced
+/// by its own consensus layer, given the broadcast rate limit, if any. Relayed
+/// broadcasts are not subject to the limit, and neither are finalization
+/// messages and records, as the other bakers need them in a timely manner.
+pub fn is_own_broadcast_allowed(
+    node: &P2PNode,
+    network_id: NetworkId,
+    packet_type: PacketType,
+) -> bool {
+    if let Some(ref limiter) = node.connection_handler.broadcast_limiter {
+        if !is_urgent_packet_type(packet_type)
+            && !lock_or_die!(limiter).allow(network_id, Instant::now())
+        {
+            node.stats.broadcasts_throttled.inc();
+            return false;
+        }
+    }
+    true
+}
+
+/// Finalization messages and records are needed by consensus in a timely
+/// manner.
+fn is_urgent_packet_type(packet_type: PacketType) -> bool {
+    matches!(packet_type, PacketType::FinalizationMessage | PacketType::FinalizationRecord)
+}
+
+/// Determine the sending priority of a packet. Direct packets and finalization
+/// messages are needed by consensus in a timely manner, so they are sent before
+/// the rest of the (gossiped) packets.
+pub fn packet_priority(packet: &NetworkPacket) -> MessageSendingPriority {
+    if let PacketDestination::Direct(..) = packet.destination {
+        return MessageSendingPriority::High;
+    }
+    match PacketType::read_from(&packet.message) {
+        Ok((packet_type, _)) if is_urgent_packet_type(packet_type) => MessageSendingPriority::High,
+        _ => MessageSendingPriority::Normal,
+    }
+}
+
 #[inline]
 fn send_message_over_network(
     node: &P2PNode,
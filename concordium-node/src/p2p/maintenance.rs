@@ -1,9 +1,12 @@
 //! Node maintenance methods.
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use chrono::prelude::*;
 use crossbeam_channel::{self, Receiver, Sender};
-use mio::{net::TcpListener, Events, Interest, Poll, Registry, Token};
+use mio::{
+    net::{TcpListener, TcpSocket},
+    Events, Interest, Poll, Registry, Token,
+};
 use nohash_hasher::BuildNoHashHasher;
 use rand::{prelude::SliceRandom, thread_rng, Rng};
 use rkv::{
@@ -25,17 +28,25 @@ use crate::{
     network::{Buckets, NetworkId, Networks},
     p2p::{
         bans::BanId,
-        connectivity::{accept, connect, connection_housekeeping, AcceptFailureReason, SELF_TOKEN},
+        bootstrappers::BootstrapperRecord,
+        connectivity::{
+            accept, connect, connection_housekeeping, process_deferred_connects,
+            AcceptFailureReason, BroadcastLimiter, CloseCause, DeferredConnects, SELF_TOKEN,
+        },
         peers::check_peers,
     },
-    plugins::consensus::{check_peer_states, update_peer_list},
+    plugins::consensus::{
+        check_baker_peers, check_peer_states, update_baking_committee, update_peer_list,
+        BakerPeerGuard,
+    },
     read_or_die, spawn_or_die,
-    stats_export_service::StatsExportService,
-    utils, write_or_die,
+    stats_export_service::{NodeState, StatsExportService},
+    utils::{self, is_acceptable_peer_ip, IpRange},
+    write_or_die,
 };
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     io::ErrorKind,
     mem,
     net::{
@@ -45,10 +56,10 @@ use std::{
     path::PathBuf,
     str::FromStr,
     sync::{
-        atomic::{AtomicU64, AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc, Mutex, RwLock,
     },
-    thread::JoinHandle,
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
@@ -60,7 +71,11 @@ pub struct NodeConfig {
     /// Do not clear persistent bans on startup.
     pub no_clear_bans: bool,
     pub disallow_multiple_peers_on_ip: bool,
+    /// The maximum number of peers, other than bootstrappers, in the same
+    /// subnet; see [`peer_subnet`](crate::utils::peer_subnet).
+    pub max_peers_per_subnet: Option<u16>,
     pub bootstrap_nodes: Vec<String>,
+    pub bootstrap_resolution_timeout: u64,
     /// Nodes to try and keep the connections to. A node will maintain two
     /// classes of connections, one which is explicitly given, and one which is
     /// discovered by bootstrapping or through other peers. The IP addresses
@@ -69,6 +84,17 @@ pub struct NodeConfig {
     pub given_addresses: RwLock<HashSet<SocketAddr>>,
     pub max_allowed_nodes: u16,
     pub relay_broadcast_percentage: f64,
+    /// The maximum number of peers a broadcast is sent to. If `None`, it is
+    /// sent to all peers in the network.
+    pub broadcast_max_peers: Option<u16>,
+    /// Broadcasts skip peers with more bytes than this waiting to be sent to
+    /// them. If `None`, no peers are skipped.
+    pub broadcast_high_water_mark: Option<usize>,
+    /// Whether loopback and link-local addresses received from peers are
+    /// connected to.
+    pub allow_local_peer_addresses: bool,
+    /// Addresses received from peers in these ranges are not connected to.
+    pub blocked_peer_addresses: Vec<IpRange>,
     pub poll_interval: u64,
     pub housekeeping_interval: u64,
     pub bootstrapping_interval: u64,
@@ -78,6 +104,31 @@ pub struct NodeConfig {
     pub max_latency: Option<u64>,
     pub hard_connection_limit: u16,
     pub conn_requests_batch_limit: u16,
+    /// The maximum number of outbound connections that can be awaiting the
+    /// handshake at any time.
+    pub max_pending_outbound_connections: u16,
+    /// Whether the node does not accept incoming connections; it then
+    /// advertises port 0 in its handshakes.
+    pub outbound_only: bool,
+    /// The number of node peers below which baking is stopped, if any.
+    pub baker_min_peers: Option<u16>,
+    /// The number of invalid messages a peer may send within a housekeeping
+    /// interval before being soft-banned, if any.
+    pub max_invalid_messages: Option<u64>,
+    /// The maximum number of networks a peer can be in.
+    pub max_peer_networks: usize,
+    /// The maximum transit time in milliseconds of accepted broadcasts, if
+    /// any.
+    pub max_broadcast_age: Option<u64>,
+    /// The networks announced to bootstrappers, if restricted.
+    pub bootstrapper_handshake_networks: Option<Networks>,
+    /// The networks announced to other nodes, if restricted.
+    pub node_handshake_networks: Option<Networks>,
+    /// The networks a bootstrapper keeps track of; all if `None`.
+    pub tracked_networks: Option<Networks>,
+    /// The time in seconds the number of node peers needs to stay below
+    /// `baker_min_peers` for baking to be stopped.
+    pub baker_min_peers_grace_period: u64,
     pub catch_up_batch_limit: i64,
     pub timeout_bucket_entry_period: u64,
     pub bucket_cleanup_interval: u64,
@@ -86,6 +137,9 @@ pub struct NodeConfig {
     pub dedup_size_short: usize,
     pub socket_read_size: usize,
     pub socket_write_size: usize,
+    /// The number of bytes of messages read from a connection per iteration of
+    /// the event loop. If `None`, connections are read until they would block.
+    pub socket_read_budget: Option<usize>,
     pub drop_rebroadcast_probability: Option<f64>,
     pub bootstrapper_peer_list_size: usize,
     pub default_network: NetworkId,
@@ -112,11 +166,31 @@ pub struct ConnectionHandler {
     #[cfg(feature = "network_dump")]
     pub log_dumper:           RwLock<Option<Sender<DumpItem>>>,
     pub conn_candidates:      Mutex<Connections>,
+    /// Outbound connection attempts waiting for the number of pending
+    /// outbound connections to drop below the configured limit.
+    pub deferred_connects:    Mutex<DeferredConnects>,
+    /// The errors that caused the most recently closed connections.
+    pub close_causes:         Mutex<VecDeque<CloseCause>>,
+    /// Bootstrappers contacted in the latest bootstrap round that haven't
+    /// completed a handshake yet.
+    pub pending_bootstraps:   Mutex<HashSet<SocketAddr>>,
+    /// Updates of the bootstrapper records that haven't been persisted yet.
+    pub bootstrapper_updates: Mutex<HashMap<SocketAddr, BootstrapperRecord>>,
+    /// Resolves the addresses of the bootstrappers; started with the first
+    /// bootstrap round.
+    pub bootstrap_resolver:   Mutex<Option<utils::BootstrapResolver>>,
+    /// Whether a bootstrap round is in progress.
+    pub bootstrapping:        AtomicBool,
     pub connections:          RwLock<Connections>,
     pub conn_changes:         ConnChanges,
     pub soft_bans:            RwLock<HashMap<BanId, Instant>>, // (id, expiry)
     pub networks:             RwLock<Networks>,
     pub deduplication_queues: DeduplicationQueues,
+    /// Limits the rate of outgoing broadcasts, if configured.
+    pub broadcast_limiter:    Option<Mutex<BroadcastLimiter>>,
+    /// Whether new connections and broadcasts are refused, so that the
+    /// existing connections can drain before a shutdown.
+    pub draining:             AtomicBool,
     pub last_bootstrap:       AtomicU64,
     pub last_peer_update:     AtomicU64,
     pub total_received:       AtomicU64,
@@ -145,11 +219,25 @@ impl ConnectionHandler {
             #[cfg(feature = "network_dump")]
             log_dumper: Default::default(),
             conn_candidates: Default::default(),
+            // there's no point in deferring more attempts than there can be connections
+            deferred_connects: Mutex::new(DeferredConnects::new(
+                conf.connection.hard_connection_limit as usize,
+            )),
+            close_causes: Default::default(),
+            pending_bootstraps: Default::default(),
+            bootstrapper_updates: Default::default(),
+            bootstrap_resolver: Default::default(),
+            bootstrapping: Default::default(),
             connections: Default::default(),
             conn_changes,
             soft_bans: Default::default(),
             networks: RwLock::new(networks),
             deduplication_queues,
+            broadcast_limiter: conf
+                .connection
+                .broadcast_rate_limit
+                .map(|rate| Mutex::new(BroadcastLimiter::new(rate))),
+            draining: Default::default(),
             last_bootstrap: Default::default(),
             last_peer_update: Default::default(),
             total_received: Default::default(),
@@ -259,15 +347,16 @@ impl P2PNode {
     /// Creates a new node and its Poll. If the node id is provided the node
     /// will be started with that Peer ID. If it is not a fresh one will be
     /// generated. The return value is a triple of the node, the socket on which
-    /// the node is listening for incoming connections, and the mio poll
-    /// that can be used to notify/poll for incoming connections.
+    /// the node is listening for incoming connections (absent if the node is
+    /// configured not to accept any), and the mio poll that can be used to
+    /// notify/poll for incoming connections.
     pub fn new(
         supplied_id: Option<P2PNodeId>,
         conf: &Config,
         peer_type: PeerType,
         stats: Arc<StatsExportService>,
         regenesis_arc: Arc<Regenesis>,
-    ) -> anyhow::Result<(Arc<Self>, TcpListener, Poll)> {
+    ) -> anyhow::Result<(Arc<Self>, Option<TcpListener>, Poll)> {
         let addr = if let Some(ref addy) = conf.common.listen_address {
             let ip_addr = addy.parse::<IpAddr>().context(
                 "Supplied listen address could not be parsed. The address must be a valid IP \
@@ -290,19 +379,28 @@ impl P2PNode {
         let id = supplied_id.unwrap_or_else(|| rand::thread_rng().gen::<P2PNodeId>());
 
         info!("My Node ID is {}", id);
-        info!("Listening on {}:{}", ip, conf.common.listen_port);
 
         let poll =
             Poll::new().context("Could not create the poll to listen for incoming connections.")?;
-        let mut server = TcpListener::bind(addr).context(format!(
-            "Could not listen on the given listen-port ({}).",
-            conf.common.listen_port
-        ))?;
         let poll_registry =
             poll.registry().try_clone().context("Could not clone the poll registry.")?;
-        poll_registry
-            .register(&mut server, SELF_TOKEN, Interest::READABLE)
-            .context("Could not register server with poll!")?;
+        let server = if conf.connection.outbound_only {
+            ensure!(
+                peer_type == PeerType::Node,
+                "A bootstrapper has to accept incoming connections."
+            );
+            info!("Running in outbound-only mode; incoming connections are not accepted");
+            None
+        } else {
+            info!("Listening on {}:{}", ip, conf.common.listen_port);
+            let mut server = bind_listener(addr, conf.connection.listen_backlog).context(
+                format!("Could not listen on the given listen-port ({}).", conf.common.listen_port),
+            )?;
+            poll_registry
+                .register(&mut server, SELF_TOKEN, Interest::READABLE)
+                .context("Could not register server with poll!")?;
+            Some(server)
+        };
 
         let own_peer_port = if let Some(own_port) = conf.common.external_port {
             own_port
@@ -324,7 +422,9 @@ impl P2PNode {
             no_bootstrap_dns: conf.connection.no_bootstrap_dns,
             no_clear_bans: conf.connection.no_clear_bans,
             disallow_multiple_peers_on_ip: conf.connection.disallow_multiple_peers_on_ip,
+            max_peers_per_subnet: conf.connection.max_peers_per_subnet,
             bootstrap_nodes: conf.connection.bootstrap_nodes.clone(),
+            bootstrap_resolution_timeout: conf.connection.bootstrap_resolution_timeout,
             given_addresses,
             max_allowed_nodes: if let Some(max) = conf.connection.max_allowed_nodes {
                 max
@@ -335,6 +435,10 @@ impl P2PNode {
                 ) as u16
             },
             relay_broadcast_percentage: conf.connection.relay_broadcast_percentage,
+            broadcast_max_peers: conf.connection.broadcast_max_peers,
+            broadcast_high_water_mark: conf.connection.broadcast_high_water_mark,
+            allow_local_peer_addresses: conf.connection.allow_local_peer_addresses,
+            blocked_peer_addresses: conf.connection.blocked_peer_addresses.clone(),
             poll_interval: conf.cli.poll_interval,
             housekeeping_interval: conf.connection.housekeeping_interval,
             bootstrapping_interval: conf.connection.bootstrapping_interval,
@@ -346,19 +450,31 @@ impl P2PNode {
             data_dir_path: conf.common.data_dir.clone(),
             max_latency: conf.connection.max_latency,
             conn_requests_batch_limit: conf.connection.conn_requests_batch_limit,
+            max_pending_outbound_connections: conf.connection.max_pending_outbound_connections,
+            outbound_only: conf.connection.outbound_only,
+            baker_min_peers: conf.cli.baker.baker_min_peers,
+            max_invalid_messages: conf.connection.max_invalid_messages,
+            max_peer_networks: conf.connection.max_peer_networks,
+            max_broadcast_age: conf.connection.max_broadcast_age,
+            bootstrapper_handshake_networks: selected_networks(
+                &conf.connection.bootstrapper_handshake_networks,
+            ),
+            node_handshake_networks: selected_networks(&conf.connection.node_handshake_networks),
+            tracked_networks: selected_networks(&conf.bootstrapper.tracked_networks),
+            baker_min_peers_grace_period: conf.cli.baker.baker_min_peers_grace_period,
             hard_connection_limit: conf.connection.hard_connection_limit,
             catch_up_batch_limit: conf.connection.catch_up_batch_limit,
-            timeout_bucket_entry_period: if peer_type == PeerType::Bootstrapper {
-                conf.bootstrapper.bootstrapper_timeout_bucket_entry_period
-            } else {
-                conf.cli.timeout_bucket_entry_period
-            },
+            timeout_bucket_entry_period: peer_type.bucket_entry_timeout(
+                conf.cli.timeout_bucket_entry_period,
+                conf.bootstrapper.bootstrapper_timeout_bucket_entry_period,
+            ),
             bucket_cleanup_interval: conf.common.bucket_cleanup_interval,
             thread_pool_size: conf.connection.thread_pool_size,
             dedup_size_long: conf.connection.dedup_size_long,
             dedup_size_short: conf.connection.dedup_size_short,
             socket_read_size: conf.connection.socket_read_size,
             socket_write_size: conf.connection.socket_write_size,
+            socket_read_budget: conf.connection.socket_read_budget,
             drop_rebroadcast_probability: match peer_type {
                 PeerType::Node => conf.cli.drop_rebroadcast_probability,
                 _ => None,
@@ -477,6 +593,21 @@ impl P2PNode {
         }
     }
 
+    /// Register an invalid message received from the given peer and return
+    /// the number of invalid messages received from it since the last
+    /// housekeeping. A peer exceeding `max_invalid_messages` is soft-banned.
+    pub fn register_invalid_message(&self, peer_id: RemotePeerId) -> u64 {
+        let num_invalid = self.bad_events.inc_invalid_messages(peer_id);
+        if let Some(max_invalid) = self.config.max_invalid_messages {
+            // only the first excess message triggers the expulsion
+            if num_invalid == max_invalid + 1 {
+                warn!("Peer {} sent more than {} invalid messages", peer_id, max_invalid);
+                self.register_conn_change(ConnChange::ExpulsionByToken(peer_id.to_token()));
+            }
+        }
+        num_invalid
+    }
+
     /// Activate the network dump feature.
     #[cfg(feature = "network_dump")]
     pub fn activate_dump(&self, path: &str, raw: bool) -> anyhow::Result<()> {
@@ -568,6 +699,8 @@ impl P2PNode {
 
     /// Shut the node down gracefully without terminating its threads.
     pub fn close(&self) -> anyhow::Result<()> {
+        self.stats.advance_node_state(NodeState::Stopping);
+        self.flush_bootstrapper_records();
         // First notify the maintenance thread to stop processing new connections or
         // network packets.
         self.stop_network();
@@ -610,13 +743,40 @@ impl P2PNode {
     }
 }
 
+/// Start listening on the given address with the given backlog of connections
+/// waiting to be accepted. Apart from the backlog, this matches
+/// `TcpListener::bind`, which uses a backlog of 1024 (the default of
+/// `--listen-backlog`).
+fn bind_listener(addr: SocketAddr, backlog: u32) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(backlog)
+}
+
+/// The networks to restrict something to, e.g. the handshake, as given on the
+/// command line; none means that there is no restriction.
+fn selected_networks(networks: &[u16]) -> Option<Networks> {
+    if networks.is_empty() {
+        None
+    } else {
+        Some(networks.iter().copied().map(NetworkId::from).collect())
+    }
+}
+
 /// Spawn the node's poll thread.
 pub fn spawn(
     node_ref: &Arc<P2PNode>,
-    mut socket_server: TcpListener,
+    mut socket_server: Option<TcpListener>,
     mut poll: Poll,
     consensus: Option<ConsensusContainer>,
 ) {
+    node_ref.stats.advance_node_state(NodeState::Bootstrapping);
     let node = Arc::clone(node_ref);
     let poll_thread = spawn_or_die!("poll loop", move || {
         let mut events = Events::with_capacity(node.config.events_queue_size);
@@ -625,11 +785,15 @@ pub fn spawn(
         let mut last_peer_list_update = 0;
         // The number of polling loop iterations since the last housekeeping.
         let mut iterations_since_housekeeping = 0;
+        let mut baker_guard = node.config.baker_min_peers.map(|min_peers| {
+            BakerPeerGuard::new(
+                min_peers.into(),
+                Duration::from_secs(node.config.baker_min_peers_grace_period),
+            )
+        });
 
-        let num_socket_threads = match node.self_peer.peer_type {
-            PeerType::Bootstrapper => 1,
-            PeerType::Node => node.config.thread_pool_size,
-        };
+        let num_socket_threads =
+            node.self_peer.peer_type.socket_threads(node.config.thread_pool_size);
         let pool = rayon::ThreadPoolBuilder::new().num_threads(num_socket_threads).build().unwrap();
         let poll_interval = Duration::from_millis(node.config.poll_interval);
 
@@ -642,6 +806,11 @@ pub fn spawn(
         // Maximum number of connection requests to process per iteration.
         let max_num_requests = node.config.conn_requests_batch_limit;
 
+        // A flag indicating whether some connections have data left to be read after
+        // exhausting their read budget. Like connection attempts, they won't produce
+        // new events, so they are read again without waiting for any.
+        let mut unread_data = false;
+
         // Process network events until signalled to terminate.
         // For each loop iteration do the following in sequence
         // - check whether there are any incoming connection requests
@@ -653,13 +822,20 @@ pub fn spawn(
         //   housekeeping, checking whether peers and connections are active.
         while !node.is_network_stopped() {
             // check for new events or wait
-            if let Err(e) = poll.poll(&mut events, Some(poll_interval)) {
+            let timeout = if unread_data {
+                Duration::from_millis(0)
+            } else {
+                poll_interval
+            };
+            if let Err(e) = poll.poll(&mut events, Some(timeout)) {
                 error!("{}", e);
                 continue;
             }
 
             // check for new connections
-            if unprocessed_attempts || events.iter().any(|event| event.token() == SELF_TOKEN) {
+            if let Some(socket_server) = socket_server.as_mut().filter(|_| {
+                unprocessed_attempts || events.iter().any(|event| event.token() == SELF_TOKEN)
+            }) {
                 let mut attempt_number = 0;
                 unprocessed_attempts = true;
                 while attempt_number < max_num_requests {
@@ -697,6 +873,8 @@ pub fn spawn(
                 process_conn_change(&node, conn_change)
             }
 
+            process_deferred_connects(&node);
+
             if let Some(ref consensus) = consensus {
                 let new_last_peer_update = node.last_peer_update();
                 if new_last_peer_update > last_peer_list_update {
@@ -707,7 +885,7 @@ pub fn spawn(
             }
 
             // perform socket reads and writes in parallel across connections
-            pool.install(|| node.process_network_events(&events));
+            unread_data = pool.install(|| node.process_network_events(&events));
 
             // Run periodic tasks
             // We prevent housekeeping from occurring too often so that new connections have
@@ -718,12 +896,18 @@ pub fn spawn(
                     >= Duration::from_secs(node.config.housekeeping_interval)
                 {
                     let attempted_bootstrap = connection_housekeeping(&node);
-                    if node.peer_type() != PeerType::Bootstrapper {
+                    if node.peer_type().monitors_connections() {
                         node.measure_connection_latencies()
                     }
 
                     let peer_stat_list = node.get_peer_stats(None);
                     check_peers(&node, &peer_stat_list, attempted_bootstrap);
+                    if let (Some(consensus), Some(guard)) = (&consensus, &mut baker_guard) {
+                        check_baker_peers(&peer_stat_list, consensus, guard);
+                    }
+                    if let Some(ref consensus) = consensus {
+                        update_baking_committee(&node, consensus);
+                    }
                     if let Err(e) = node.measure_throughput() {
                         error!("Could not measure throughput: {}", e);
                     }
@@ -755,13 +939,24 @@ pub fn spawn(
         // connection handler is used in many different places, including in the rpc
         // module. If we did that there would be no need for clearing connection
         // collections here.
-        lock_or_die!(node.conn_candidates()).clear();
-        write_or_die!(node.connections()).clear();
+        let remaining = {
+            let mut candidates = lock_or_die!(node.conn_candidates());
+            let mut connections = write_or_die!(node.connections());
+            let remaining = candidates.len() + connections.len();
+            candidates.clear();
+            connections.clear();
+            remaining
+        };
+        if remaining > 0 {
+            info!("Force-closed {} remaining connection(s)", remaining);
+        }
         write_or_die!(node.peers).clear();
         // Stop listening and close the socket. The socket is closed when the thread
         // terminates via drop.
-        if let Err(e) = poll.registry().deregister(&mut socket_server) {
-            error!("Could not deregister listen socket poll: {}", e);
+        if let Some(ref mut socket_server) = socket_server {
+            if let Err(e) = poll.registry().deregister(socket_server) {
+                error!("Could not deregister listen socket poll: {}", e);
+            }
         }
         info!("Network layer has been shut down.");
     });
@@ -808,9 +1003,7 @@ fn process_conn_change(node: &Arc<P2PNode>, conn_change: ConnChange) {
         }
         ConnChange::NewPeers(mut peers) => {
             let mut new_peers = 0;
-            let current_peers = node.get_peer_stats(Some(PeerType::Node));
-
-            let curr_peer_count = current_peers.len();
+            let curr_peer_count = node.count_peers(Some(PeerType::Node));
 
             // Shuffle the peers we received try to discover more useful peers over time
             // and not get stuck continuously connecting to useless ones, and then dropping
@@ -825,6 +1018,17 @@ fn process_conn_change(node: &Arc<P2PNode>, conn_change: ConnChange) {
                 }
 
                 trace!("Got info for peer {} ({})", peer.id, peer.addr);
+                if !is_acceptable_peer_ip(
+                    peer.addr.ip(),
+                    node.config.allow_local_peer_addresses,
+                    &node.config.blocked_peer_addresses,
+                ) {
+                    debug!(
+                        "Not connecting to peer {}: its address {} is blocked",
+                        peer.id, peer.addr
+                    );
+                    continue;
+                }
                 if let Err(e) = connect(node, PeerType::Node, peer.addr, Some(peer.id), true) {
                     debug!("Could not connect to discovered peer {}", e);
                 } else {
@@ -855,26 +1059,67 @@ fn process_conn_change(node: &Arc<P2PNode>, conn_change: ConnChange) {
     }
 }
 
-/// Try to bootstrap the node based on the addresses in the config.
+/// Try to bootstrap the node based on the addresses in the config. The
+/// addresses are resolved on a separate thread, so that a slow resolver
+/// doesn't hold up the caller, and no new round is started while the previous
+/// one is still in progress.
 pub fn attempt_bootstrap(node: &Arc<P2PNode>) {
     if !node.config.no_net {
+        // the bootstrappers aren't contacted while draining, so the round
+        // must not count against them
+        if node.is_draining() {
+            debug!("Not bootstrapping while draining");
+            return;
+        }
+        if node.connection_handler.bootstrapping.swap(true, Ordering::AcqRel) {
+            debug!("Not bootstrapping: the previous round is still in progress");
+            return;
+        }
         info!("Attempting to bootstrap");
 
-        let bootstrap_nodes = utils::get_bootstrap_nodes(&node.config.bootstrap_nodes);
-
-        match bootstrap_nodes {
-            Ok(nodes) => {
-                for addr in nodes {
-                    info!("Using bootstrapper {}", addr);
-                    node.register_conn_change(ConnChange::NewConn {
-                        addr,
-                        peer_type: PeerType::Bootstrapper,
-                        given: false,
-                    });
-                }
+        let node_ref = Arc::clone(node);
+        let spawned = thread::Builder::new().name("bootstrap".to_owned()).spawn(move || {
+            bootstrap(&node_ref);
+            node_ref.connection_handler.bootstrapping.store(false, Ordering::Release);
+        });
+        if let Err(e) = spawned {
+            error!("Can't bootstrap: {}", e);
+            node.connection_handler.bootstrapping.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Resolve the configured bootstrappers and connect to them.
+fn bootstrap(node: &P2PNode) {
+    let deadline = Instant::now() + Duration::from_secs(node.config.bootstrap_resolution_timeout);
+    let mut resolver = lock_or_die!(node.connection_handler.bootstrap_resolver);
+    let resolver = match &mut *resolver {
+        Some(resolver) => resolver,
+        none => match utils::BootstrapResolver::new(node.config.bootstrap_nodes.len()) {
+            Ok(resolver) => none.insert(resolver),
+            Err(e) => {
+                error!("Can't start resolving the bootstrappers: {}", e);
+                return;
+            }
+        },
+    };
+
+    match resolver.resolve(&node.config.bootstrap_nodes, deadline) {
+        Ok(_) if node.is_draining() => debug!("Not bootstrapping while draining"),
+        Ok(nodes) => {
+            // prefer the bootstrappers that proved useful before
+            let nodes = node.rank_bootstrappers(nodes);
+            node.register_bootstrap_round(&nodes);
+            for addr in nodes {
+                info!("Using bootstrapper {}", addr);
+                node.register_conn_change(ConnChange::NewConn {
+                    addr,
+                    peer_type: PeerType::Bootstrapper,
+                    given: false,
+                });
             }
-            Err(e) => error!("Can't bootstrap: {:?}", e),
         }
+        Err(e) => error!("Can't bootstrap: {:?}", e),
     }
 }
 
@@ -1,6 +1,7 @@
 //! Central node object handling.
 
 pub mod bans;
+pub mod bootstrappers;
 pub mod connectivity;
 pub mod maintenance;
 pub mod peers;
@@ -10,11 +11,27 @@ pub use self::maintenance::{Connections, P2PNode};
 #[cfg(test)]
 mod tests {
     use crate::{
-        common::{p2p_peer::RemotePeerId, PeerType},
-        p2p::bans::PersistedBanId,
+        common::{p2p_peer::RemotePeerId, P2PPeer, PeerType},
+        connection::ConnChange,
+        lock_or_die,
+        network::NetworkId,
+        p2p::{
+            bans::PersistedBanId,
+            connectivity::{connect, send_broadcast_message},
+            maintenance::attempt_bootstrap,
+            P2PNode,
+        },
+        read_or_die,
+        stats_export_service::NodeState,
         test_utils::*,
     };
-    use std::net::IpAddr;
+    use std::{
+        collections::HashSet,
+        net::{IpAddr, TcpListener},
+        sync::{atomic::Ordering, Arc},
+        thread,
+        time::{Duration, Instant},
+    };
 
     #[test]
     fn test_ban_functionalities() -> anyhow::Result<()> {
@@ -65,4 +82,477 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_pending_outbound_connections_limit() -> anyhow::Result<()> {
+        const LIMIT: u16 = 2;
+        const ADDRESSES: usize = 10;
+
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.connection.max_pending_outbound_connections = LIMIT;
+        let (node, dp) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+
+        // listeners that never accept, so that outbound connections to them remain
+        // pending until the handshake times out
+        let listeners = (0..ADDRESSES)
+            .map(|_| TcpListener::bind(("127.0.0.1", next_available_port())))
+            .collect::<Result<Vec<_>, _>>()?;
+        for listener in &listeners {
+            connect(&node, PeerType::Node, listener.local_addr()?, None, false)?;
+        }
+
+        let pending = lock_or_die!(node.conn_candidates())
+            .values()
+            .filter(|conn| conn.low_level.is_initiator())
+            .count();
+        let deferred = lock_or_die!(node.connection_handler.deferred_connects).len();
+        assert_eq!(pending, LIMIT as usize);
+        assert_eq!(deferred, ADDRESSES - LIMIT as usize);
+
+        // retrying a delayed address doesn't queue it again
+        connect(&node, PeerType::Node, listeners[ADDRESSES - 1].local_addr()?, None, false)?;
+        assert_eq!(lock_or_die!(node.connection_handler.deferred_connects).len(), deferred);
+
+        stop_node_delete_dirs(dp, node);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bucket_export_import() -> anyhow::Result<()> {
+        let (node_1, dp_1) =
+            make_node_and_sync(next_available_port(), vec![100, 200], PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100, 200], PeerType::Node, vec![])?;
+        crate::test_utils::connect(&node_1, &node_2);
+        await_handshakes(&node_1);
+        await_handshakes(&node_2);
+
+        let exported = node_1.export_buckets();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].id, node_2.id().to_string());
+        assert_eq!(exported[0].networks, vec![100, 200]);
+
+        // the export survives a serialization round trip and seeds a fresh node
+        let serialized = serde_json::to_string(&exported)?;
+        let (node_3, dp_3) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        node_3.import_buckets(&serde_json::from_str::<Vec<_>>(&serialized)?)?;
+        assert_eq!(node_3.export_buckets(), exported);
+
+        stop_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+        stop_node_delete_dirs(dp_3, node_3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_draining() -> anyhow::Result<()> {
+        let (node_1, dp_1) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (node_3, dp_3) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        crate::test_utils::connect(&node_1, &node_2);
+        await_handshakes(&node_1);
+        await_handshakes(&node_2);
+
+        node_1.begin_draining();
+        assert!(node_1.is_draining());
+        assert_eq!(node_1.stats.draining.get(), 1);
+
+        // new connections are refused in both directions
+        assert!(connect(&node_1, PeerType::Node, node_3.self_peer.addr, None, false).is_err());
+        crate::test_utils::connect(&node_3, &node_1);
+        thread::sleep(Duration::from_millis(500));
+        assert!(read_or_die!(node_3.connections()).is_empty());
+
+        // while the existing connection persists
+        let peers = node_1.get_peer_stats(None);
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].self_id, node_2.id());
+        assert!(node_1.await_drained(1, Instant::now()));
+        assert!(!node_1.await_drained(0, Instant::now() + Duration::from_millis(200)));
+
+        stop_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+        stop_node_delete_dirs(dp_3, node_3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_state() -> anyhow::Result<()> {
+        let (node_1, dp_1) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        assert_eq!(node_1.stats.node_state.get(), NodeState::Bootstrapping as i64);
+
+        crate::test_utils::connect(&node_1, &node_2);
+        await_handshakes(&node_1);
+        assert_eq!(node_1.stats.node_state.get(), NodeState::Ready as i64);
+
+        node_1.begin_draining();
+        assert_eq!(node_1.stats.node_state.get(), NodeState::Draining as i64);
+
+        node_1.close()?;
+        assert_eq!(node_1.stats.node_state.get(), NodeState::Stopping as i64);
+
+        wait_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_with_stalled_peer() -> anyhow::Result<()> {
+        let (node, dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+
+        // a peer that connects but never reads nor writes anything
+        let _stalled = std::net::TcpStream::connect(node.self_peer.addr)?;
+        await_condition(Duration::from_secs(5), "the connection to be accepted", || {
+            !lock_or_die!(node.conn_candidates()).is_empty()
+        });
+
+        // the stalled connection is closed rather than waited for
+        let start = Instant::now();
+        stop_node_delete_dirs(dp, node);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_each_peer_stat() -> anyhow::Result<()> {
+        let (node, dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (peer, peer_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (bootstrapper, bootstrapper_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Bootstrapper, vec![])?;
+        crate::test_utils::connect(&node, &peer);
+        crate::test_utils::connect(&node, &bootstrapper);
+        await_condition(Duration::from_secs(5), "the peers", || {
+            node.get_peer_stats(None).len() >= 2
+        });
+
+        for peer_type in [None, Some(PeerType::Node), Some(PeerType::Bootstrapper)] {
+            let mut seen = HashSet::new();
+            node.for_each_peer_stat(peer_type, |stats| {
+                seen.insert(stats.local_id);
+            });
+            let expected = node
+                .get_peer_stats(peer_type)
+                .iter()
+                .map(|stats| stats.local_id)
+                .collect::<HashSet<_>>();
+            assert_eq!(seen, expected);
+            assert_eq!(node.count_peers(peer_type), seen.len());
+            assert_eq!(
+                seen.len(),
+                if peer_type.is_none() {
+                    2
+                } else {
+                    1
+                }
+            );
+        }
+
+        stop_node_delete_dirs(dp, node);
+        stop_node_delete_dirs(peer_dp, peer);
+        stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_peer_addresses_are_ignored() -> anyhow::Result<()> {
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.connection.allow_local_peer_addresses = false;
+        let (strict, strict_dp) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+        let (permissive, permissive_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (peer, peer_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+
+        let peer_list = || {
+            ConnChange::NewPeers(vec![P2PPeer {
+                id:        peer.id(),
+                addr:      peer.self_peer.addr,
+                peer_type: PeerType::Node,
+            }])
+        };
+
+        // the peer's local address is only connected to if allowed
+        permissive.register_conn_change(peer_list());
+        await_handshakes(&permissive);
+        await_handshakes(&peer);
+        strict.register_conn_change(peer_list());
+        thread::sleep(Duration::from_millis(500));
+        assert!(lock_or_die!(strict.conn_candidates()).is_empty());
+        assert!(read_or_die!(strict.connections()).is_empty());
+        assert_eq!(read_or_die!(peer.connections()).len(), 1);
+
+        stop_node_delete_dirs(strict_dp, strict);
+        stop_node_delete_dirs(permissive_dp, permissive);
+        stop_node_delete_dirs(peer_dp, peer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outbound_only() -> anyhow::Result<()> {
+        let port = next_available_port();
+        let mut config = get_test_config(port, vec![100]);
+        config.connection.outbound_only = true;
+        let (node_1, dp_1) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+
+        // nothing listens on the node's port
+        drop(TcpListener::bind(("127.0.0.1", port))?);
+
+        // but it can still connect to other peers
+        crate::test_utils::connect(&node_1, &node_2);
+        await_handshakes(&node_1);
+        await_handshakes(&node_2);
+        assert_eq!(node_1.get_peer_stats(None)[0].self_id, node_2.id());
+        assert_eq!(node_2.get_peer_stats(None)[0].self_id, node_1.id());
+
+        // it advertises no port, so it is not kept in its peer's buckets
+        assert_eq!(node_2.get_peer_stats(None)[0].external_port, 0);
+        assert!(read_or_die!(node_2.buckets()).is_empty());
+
+        stop_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_decisions() -> anyhow::Result<()> {
+        let (bootstrapper, bootstrapper_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Bootstrapper, vec![])?;
+        let (node_1, dp_1) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        let bootstrapper_addr = bootstrapper.self_peer.addr;
+
+        connect(&node_2, PeerType::Bootstrapper, bootstrapper_addr, None, false)?;
+        await_handshakes(&node_2);
+        // the bootstrapper sends its peer list right away, from which the node
+        // connects to the other one
+        connect(&node_1, PeerType::Bootstrapper, bootstrapper_addr, None, false)?;
+        await_condition(Duration::from_secs(5), "the peer list", || {
+            node_1.count_peers(Some(PeerType::Node)) >= 1
+        });
+        assert_eq!(node_1.count_peers(Some(PeerType::Bootstrapper)), 1);
+
+        // only the bootstrapper is ranked, by its handshake and the peer it returned
+        let ranking = node_1.get_bootstrapper_ranking()?;
+        assert_eq!(ranking.len(), 1);
+        assert_eq!(ranking[0].0, bootstrapper_addr);
+        assert_eq!(ranking[0].1.handshakes, 1);
+        assert_eq!(ranking[0].1.peers_returned, 1);
+
+        // the nodes, but not the bootstrapper, are kept in the buckets
+        await_condition(Duration::from_secs(5), "the handshake", || {
+            !read_or_die!(node_2.buckets()).is_empty()
+        });
+        assert_eq!(read_or_die!(node_1.buckets()).len(), 1);
+        assert_eq!(read_or_die!(node_2.buckets()).len(), 1);
+        assert_eq!(read_or_die!(bootstrapper.buckets()).len(), 2);
+
+        // broadcasts are only sent to the nodes
+        let sent =
+            send_broadcast_message(&node_1, vec![], NetworkId::from(100), Arc::from(&[0][..]));
+        assert_eq!(sent, 1);
+
+        stop_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+        stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_handshake_networks() -> anyhow::Result<()> {
+        let mut config = get_test_config(next_available_port(), vec![100, 200]);
+        config.connection.bootstrapper_handshake_networks = vec![100];
+        let (node, dp) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+        let (bootstrapper, bootstrapper_dp) = make_node_and_sync(
+            next_available_port(),
+            vec![100, 200],
+            PeerType::Bootstrapper,
+            vec![],
+        )?;
+        let (peer, peer_dp) =
+            make_node_and_sync(next_available_port(), vec![100, 200], PeerType::Node, vec![])?;
+
+        node.register_conn_change(ConnChange::NewConn {
+            addr:      bootstrapper.self_peer.addr,
+            peer_type: PeerType::Bootstrapper,
+            given:     false,
+        });
+        crate::test_utils::connect(&node, &peer);
+        await_handshakes(&node);
+        await_handshakes(&bootstrapper);
+        await_handshakes(&peer);
+
+        let announced = |observer: &P2PNode| {
+            let stats = observer.get_peer_stats(None);
+            assert_eq!(stats.len(), 1);
+            let mut networks = stats[0].networks.iter().map(|net| net.id).collect::<Vec<_>>();
+            networks.sort_unstable();
+            networks
+        };
+        assert_eq!(announced(&bootstrapper), vec![100]);
+        assert_eq!(announced(&peer), vec![100, 200]);
+
+        // networks joined later are only announced to the same peer types
+        node.send_join_network(NetworkId::from(300));
+        await_condition(Duration::from_secs(5), "the join request", || {
+            announced(&peer) == vec![100, 200, 300]
+        });
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(announced(&bootstrapper), vec![100]);
+
+        stop_node_delete_dirs(dp, node);
+        stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+        stop_node_delete_dirs(peer_dp, peer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracked_networks() -> anyhow::Result<()> {
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.bootstrapper.tracked_networks = vec![100];
+        let (bootstrapper, bootstrapper_dp) =
+            make_node_and_sync_with_config(config, PeerType::Bootstrapper, vec![])?;
+        let (node, dp) =
+            make_node_and_sync(next_available_port(), vec![100, 200], PeerType::Node, vec![])?;
+
+        node.register_conn_change(ConnChange::NewConn {
+            addr:      bootstrapper.self_peer.addr,
+            peer_type: PeerType::Bootstrapper,
+            given:     false,
+        });
+        await_handshakes(&node);
+        await_handshakes(&bootstrapper);
+
+        let bucketed_networks = || {
+            let entries = bootstrapper.export_buckets();
+            assert_eq!(entries.len(), 1);
+            entries[0].networks.clone()
+        };
+        // the untracked network announced in the handshake is not bucketed
+        assert_eq!(bucketed_networks(), vec![100]);
+
+        // and neither is one the peer joins later
+        node.send_join_network(NetworkId::from(300));
+        let untracked =
+            bootstrapper.stats.unwanted_messages.with_label_values(&["untracked network"]);
+        await_condition(Duration::from_secs(5), "the join request", || untracked.get() > 0);
+        assert_eq!(bucketed_networks(), vec![100]);
+
+        stop_node_delete_dirs(dp, node);
+        stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_burst_is_accepted() -> anyhow::Result<()> {
+        const CONNECTIONS: usize = 20;
+        const BACKLOG: u32 = 4;
+
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.connection.listen_backlog = BACKLOG;
+        let (node, dp) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+        let addr = node.self_peer.addr;
+
+        // keep the node from accepting while the burst arrives, so that it
+        // overflows the backlog
+        let candidates = lock_or_die!(node.conn_candidates());
+        let connecting = (0..CONNECTIONS)
+            .map(|_| thread::spawn(move || std::net::TcpStream::connect(addr)))
+            .collect::<Vec<_>>();
+        thread::sleep(Duration::from_millis(500));
+        drop(candidates);
+
+        // the connections that didn't fit are retried by the operating system
+        // and accepted over several iterations of the poll loop
+        let streams = connecting
+            .into_iter()
+            .map(|handle| handle.join().expect("the connecting thread panicked"))
+            .collect::<Result<Vec<_>, _>>()?;
+        await_condition(Duration::from_secs(15), "the connections to be accepted", || {
+            lock_or_die!(node.conn_candidates()).len() >= CONNECTIONS
+        });
+
+        drop(streams);
+        stop_node_delete_dirs(dp, node);
+
+        Ok(())
+    }
+
+    fn await_bootstrap_round(node: &P2PNode) {
+        await_condition(Duration::from_secs(5), "the bootstrap round", || {
+            !node.connection_handler.bootstrapping.load(Ordering::Acquire)
+        });
+    }
+
+    #[test]
+    fn test_bootstrapper_ranking() -> anyhow::Result<()> {
+        let (bootstrapper, bootstrapper_dp) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Bootstrapper, vec![])?;
+        // a listener that never accepts, so that the handshake never completes
+        let dead = TcpListener::bind(("127.0.0.1", next_available_port()))?;
+        let good_addr = bootstrapper.self_peer.addr;
+        let dead_addr = dead.local_addr()?;
+
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.cli.no_network = false;
+        config.connection.bootstrap_nodes = vec![dead_addr.to_string(), good_addr.to_string()];
+        let (node, dp) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+
+        attempt_bootstrap(&node);
+        await_condition(Duration::from_secs(5), "the bootstrapper", || {
+            !node.get_peer_stats(Some(PeerType::Bootstrapper)).is_empty()
+        });
+        await_bootstrap_round(&node);
+
+        // the next round records the failure of the dead bootstrapper
+        attempt_bootstrap(&node);
+        await_bootstrap_round(&node);
+        let ranking = node.get_bootstrapper_ranking()?;
+        assert_eq!(ranking.len(), 2);
+        assert_eq!(ranking[0].0, good_addr);
+        assert_eq!(ranking[0].1.handshakes, 1);
+        assert_eq!(ranking[1].0, dead_addr);
+        assert_eq!(ranking[1].1.failures, 1);
+        assert_eq!(node.rank_bootstrappers(vec![dead_addr, good_addr]), vec![good_addr, dead_addr]);
+
+        // the records are the same once persisted
+        node.flush_bootstrapper_records();
+        assert!(lock_or_die!(node.connection_handler.bootstrapper_updates).is_empty());
+        assert_eq!(node.get_bootstrapper_ranking()?, ranking);
+
+        // rounds skipped while draining don't count as failures
+        node.begin_draining();
+        attempt_bootstrap(&node);
+        await_bootstrap_round(&node);
+        assert_eq!(node.get_bootstrapper_ranking()?, ranking);
+
+        stop_node_delete_dirs(dp, node);
+        stop_node_delete_dirs(bootstrapper_dp, bootstrapper);
+
+        Ok(())
+    }
 }
@@ -4,49 +4,97 @@ use crate::{
     common::{get_current_stamp, p2p_peer::RemotePeerId, PeerStats, PeerType},
     connection::Connection,
     netmsg,
-    network::NetworkRequest,
+    network::{buckets::BucketEntry, NetworkRequest},
     p2p::{maintenance::attempt_bootstrap, P2PNode},
-    read_or_die,
+    read_or_die, write_or_die,
 };
 use anyhow::ensure;
 use chrono::Utc;
 use prometheus::core::Atomic;
 use std::sync::{atomic::Ordering, Arc};
 
+fn is_of_peer_type(conn: &Connection, peer_type: Option<PeerType>) -> bool {
+    peer_type.is_none() || peer_type == Some(conn.remote_peer_type())
+}
+
+fn conn_peer_stats(conn: &Connection) -> PeerStats {
+    PeerStats::new(
+        conn.remote_peer.local_id,
+        conn.remote_peer.self_id.unwrap(), // safe - always available post-handshake
+        conn.remote_addr(),
+        conn.remote_peer_external_port(),
+        conn.remote_peer_type(),
+        conn.remote_end_networks.clone(),
+        &conn.stats,
+    )
+}
+
 impl P2PNode {
     /// Obtain the list of statistics from all the peers, optionally of a
     /// specific peer type.
     pub fn get_peer_stats(&self, peer_type: Option<PeerType>) -> Vec<PeerStats> {
+        let conns = read_or_die!(self.connections());
+        let mut peer_stats = Vec::with_capacity(conns.len());
+        peer_stats.extend(
+            conns.values().filter(|conn| is_of_peer_type(conn, peer_type)).map(conn_peer_stats),
+        );
+        peer_stats
+    }
+
+    /// Apply the given function to the statistics of all the peers, optionally
+    /// of a specific peer type, without collecting them first.
+    /// The connections are locked for reading while `f` runs.
+    pub fn for_each_peer_stat(&self, peer_type: Option<PeerType>, mut f: impl FnMut(PeerStats)) {
+        for conn in read_or_die!(self.connections())
+            .values()
+            .filter(|conn| is_of_peer_type(conn, peer_type))
+        {
+            f(conn_peer_stats(conn))
+        }
+    }
+
+    /// Count the peers, optionally of a specific peer type, without obtaining
+    /// their statistics.
+    pub fn count_peers(&self, peer_type: Option<PeerType>) -> usize {
         read_or_die!(self.connections())
             .values()
-            .filter(|conn| peer_type.is_none() || peer_type == Some(conn.remote_peer_type()))
-            .map(|conn| {
-                PeerStats::new(
-                    conn.remote_peer.local_id,
-                    conn.remote_peer.self_id.unwrap(), // safe - always available post-handshake
-                    conn.remote_addr(),
-                    conn.remote_peer_external_port(),
-                    conn.remote_peer_type(),
-                    &conn.stats,
-                )
-            })
-            .collect()
+            .filter(|conn| is_of_peer_type(conn, peer_type))
+            .count()
     }
 
     /// Prints information about all the peers.
     pub fn print_stats(&self, peer_stat_list: &[PeerStats]) {
         for (i, peer) in peer_stat_list.iter().enumerate() {
+            let mut networks = peer.networks.iter().map(|net| net.id).collect::<Vec<_>>();
+            networks.sort_unstable();
             trace!(
-                "Peer {}({}): {}/{}/{}",
+                "Peer {}({}): {}/{}/{}/networks {:?}",
                 i,
                 peer.self_id,
                 peer.local_id,
                 peer.addr,
-                peer.peer_type
+                peer.peer_type,
+                networks
             );
         }
     }
 
+    /// Export a description of all the bucketed peers, e.g. in order to
+    /// analyze the peer topology.
+    pub fn export_buckets(&self) -> Vec<BucketEntry> { read_or_die!(self.buckets()).export() }
+
+    /// Pre-seed the buckets with the described peers, e.g. ones obtained via
+    /// `export_buckets`.
+    pub fn import_buckets(&self, entries: &[BucketEntry]) -> anyhow::Result<()> {
+        let next_token = &self.connection_handler.next_token;
+        write_or_die!(self.buckets()).import(
+            entries,
+            || RemotePeerId::from(next_token.fetch_add(1, Ordering::SeqCst)),
+            self.config.max_peers_per_subnet,
+            &self.stats.peer_bucket_size,
+        )
+    }
+
     /// Obtain the node ids of all the node peers.
     pub fn get_node_peer_tokens(&self) -> Vec<RemotePeerId> {
         self.get_peer_stats(Some(PeerType::Node)).into_iter().map(|stats| stats.local_id).collect()
@@ -80,19 +128,21 @@ impl P2PNode {
     }
 
     fn send_get_peers(&self) {
-        let request =
-            NetworkRequest::GetPeers(read_or_die!(self.networks()).iter().copied().collect());
-        let message = netmsg!(NetworkRequest, request);
-        let filter = |_: &Connection| true;
-
-        let mut buf = Vec::with_capacity(256);
-
-        if let Err(e) = message
-            .serialize(&mut buf)
-            .map(|_| buf)
-            .map(|buf| self.send_over_all_connections(&buf, &filter))
-        {
-            error!("Can't send a GetPeers request: {}", e);
+        // the request contains our networks, so it is tailored to each type of peer
+        for &peer_type in &[PeerType::Node, PeerType::Bootstrapper] {
+            let request = NetworkRequest::GetPeers(self.announced_networks(peer_type));
+            let message = netmsg!(NetworkRequest, request);
+            let filter = |conn: &Connection| conn.remote_peer_type() == peer_type;
+
+            let mut buf = Vec::with_capacity(256);
+
+            if let Err(e) = message
+                .serialize(&mut buf)
+                .map(|_| buf)
+                .map(|buf| self.send_over_all_connections(&buf, &filter))
+            {
+                error!("Can't send a GetPeers request: {}", e);
+            }
         }
     }
 
@@ -122,7 +172,7 @@ pub fn check_peers(node: &Arc<P2PNode>, peer_stats: &[PeerStats], attempted_boot
         node.print_stats(peer_stats);
     }
 
-    if node.self_peer.peer_type == PeerType::Node {
+    if node.self_peer.peer_type.maintains_peer_count() {
         let node_count = peer_stats.iter().filter(|peer| peer.peer_type == PeerType::Node).count();
 
         if !node.config.no_net && node_count < node.config.desired_nodes_count as usize {
@@ -3,7 +3,7 @@ use anyhow::{bail, ensure};
 use crossbeam_channel::TrySendError;
 
 use crate::{
-    common::{get_current_stamp, p2p_peer::RemotePeerId},
+    common::{get_current_stamp, p2p_peer::RemotePeerId, PeerStats, PeerType},
     configuration::{self, MAX_CATCH_UP_TIME},
     connection::ConnChange,
     consensus_ffi::{
@@ -18,19 +18,19 @@ use crate::{
         messaging::{ConsensusMessage, DistributionMode, MessageType},
     },
     p2p::{
-        connectivity::{send_broadcast_message, send_direct_message},
+        connectivity::{is_own_broadcast_allowed, send_broadcast_message, send_direct_message},
         P2PNode,
     },
     read_or_die, write_or_die,
 };
-use concordium_base::common::Deserial;
+use concordium_base::hashes::BlockHash;
 
 use std::{
     collections::hash_map::Entry::*,
-    convert::TryFrom,
-    io::{Cursor, Read},
+    io::Read,
     path::Path,
     sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
 
 /// Initializes the consensus layer with the given setup.
@@ -97,6 +97,7 @@ pub fn get_baker_data(
     };
 
     let private_data = if let Some(path) = &conf.baker_credentials_file {
+        check_baker_credentials_permissions(path, conf.strict_baker_credentials_permissions)?;
         let read_data = match std::fs::read(path) {
             Ok(read_data) => read_data,
             Err(e) => bail!("Cannot open the baker credentials file ({})!", e),
@@ -123,6 +124,57 @@ pub fn get_baker_data(
     Ok((genesis_data, private_data))
 }
 
+/// Checks that the genesis block hash reported by consensus, i.e., the first
+/// of the given genesis block hashes, matches the expected one, if any.
+pub fn check_genesis_hash(
+    genesis_blocks: &[BlockHash],
+    expected: Option<&BlockHash>,
+) -> anyhow::Result<()> {
+    if let Some(expected) = expected {
+        match genesis_blocks.first() {
+            Some(genesis) => ensure!(
+                genesis == expected,
+                "The genesis block hash {} of the genesis data does not match the expected \
+                 genesis block hash {}. Please verify that the genesis data file is for the \
+                 correct chain.",
+                genesis,
+                expected
+            ),
+            None => bail!("Cannot check the genesis block hash: consensus did not report it."),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the baker credentials file is only accessible by its owner. If
+/// it is readable or writable by anyone else, this is either logged as a
+/// warning or, if `strict` is set, reported as an error.
+#[cfg(unix)]
+fn check_baker_credentials_permissions(path: &Path, strict: bool) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.permissions().mode(),
+        Err(e) => bail!("Cannot open the baker credentials file ({})!", e),
+    };
+    if mode & 0o077 != 0 {
+        let msg = format!(
+            "The baker credentials file {} is accessible by other users (mode {:o}); it should \
+             only be accessible by its owner (mode 600).",
+            path.display(),
+            mode & 0o777
+        );
+        if strict {
+            bail!(msg);
+        }
+        warn!("{}", msg);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_baker_credentials_permissions(_path: &Path, _strict: bool) -> anyhow::Result<()> { Ok(()) }
+
 /// Handles packets coming from other peers.
 pub fn handle_pkt_out(
     node: &P2PNode,
@@ -131,17 +183,18 @@ pub fn handle_pkt_out(
     msg: Vec<u8>,
     is_broadcast: bool,
 ) -> anyhow::Result<()> {
-    ensure!(!msg.is_empty(), "Packet payload can't be empty");
-    let consensus_type = u8::deserial(&mut Cursor::new(&msg[..1]))?;
-    let packet_type = PacketType::try_from(consensus_type)?;
+    let (packet_type, payload) = PacketType::read_from(&msg)?;
+    if packet_type != PacketType::Transaction {
+        packet_type.read_genesis_index(payload)?;
+    }
+    // length of the actual payload, i.e. without the 1-byte tag.
+    let payload_len = payload.len();
 
     let distribution_mode = if is_broadcast {
         DistributionMode::Broadcast
     } else {
         DistributionMode::Direct
     };
-    // length of the actual payload. The message has a 1-byte tag prepended to it.
-    let payload_len = msg[1..].len();
 
     let request = ConsensusMessage::new(
         MessageType::Inbound(peer_id, distribution_mode),
@@ -215,6 +268,10 @@ pub fn handle_consensus_outbound_msg(
                 (message.payload.clone(), message.variant),
             );
         }
+    } else if message.target_peer().is_none()
+        && !is_own_broadcast_allowed(node, node.config.default_network, message.variant)
+    {
+        debug!("Not broadcasting a {} due to the broadcast rate limit", message.variant);
     } else {
         send_consensus_msg_to_net(
             node,
@@ -292,23 +349,23 @@ fn send_msg_to_consensus(
 
     let consensus_response = match message.variant {
         Block => {
-            let genesis_index = u32::deserial(&mut Cursor::new(&payload[..4]))?;
-            consensus.receive_block(genesis_index, &payload[4..])
+            let (genesis_index, content) = message.variant.read_genesis_index(payload)?;
+            consensus.receive_block(genesis_index, content)
         }
         FinalizationMessage => {
-            let genesis_index = u32::deserial(&mut Cursor::new(&payload[..4]))?;
-            (consensus.send_finalization(genesis_index, &payload[4..]), Option::None)
+            let (genesis_index, content) = message.variant.read_genesis_index(payload)?;
+            (consensus.send_finalization(genesis_index, content), Option::None)
         }
         FinalizationRecord => {
-            let genesis_index = u32::deserial(&mut Cursor::new(&payload[..4]))?;
-            (consensus.send_finalization_record(genesis_index, &payload[4..]), Option::None)
+            let (genesis_index, content) = message.variant.read_genesis_index(payload)?;
+            (consensus.send_finalization_record(genesis_index, content), Option::None)
         }
         CatchUpStatus => {
-            let genesis_index = u32::deserial(&mut Cursor::new(&payload[..4]))?;
+            let (genesis_index, content) = message.variant.read_genesis_index(payload)?;
             (
                 consensus.receive_catch_up_status(
                     genesis_index,
-                    &payload[4..],
+                    content,
                     source_id,
                     node.config.catch_up_batch_limit,
                 ),
@@ -317,19 +374,28 @@ fn send_msg_to_consensus(
         }
         Transaction => (consensus.send_transaction(payload).1, Option::None),
     };
+    register_consensus_result(node, source_id, message, consensus_response.0);
+
+    Ok(consensus_response)
+}
 
-    if consensus_response.0.is_acceptable() {
+/// Count a message rejected by consensus against the peer that sent it.
+fn register_consensus_result(
+    node: &P2PNode,
+    source_id: RemotePeerId,
+    message: &ConsensusMessage,
+    result: ConsensusFfiResponse,
+) {
+    if result.is_acceptable() {
         debug!("Processed a {} from {}", message.variant, source_id);
     } else {
-        let num_bad_events = node.bad_events.inc_invalid_messages(source_id);
+        let num_bad_events = node.register_invalid_message(source_id);
         // we do log some invalid messages to both ease debugging and see problems in
         // normal circumstances
         if num_bad_events < 10 {
-            warn!("Couldn't process a {} due to error code {:?}", message, consensus_response.0);
+            warn!("Couldn't process a {} due to error code {:?}", message, result);
         }
     }
-
-    Ok(consensus_response)
 }
 
 fn send_consensus_msg_to_net(
@@ -414,6 +480,87 @@ fn try_catch_up(node: &P2PNode, consensus: &ConsensusContainer, peers: &mut Peer
     }
 }
 
+/// A change to the state of the baker, as decided by a `BakerPeerGuard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BakerTransition {
+    Stop,
+    Start,
+}
+
+/// Keeps the node from baking in isolation: it decides to stop the baker when
+/// the number of node peers stays below a floor for a grace period, and to
+/// restart it once the peers recover.
+pub struct BakerPeerGuard {
+    min_peers:    usize,
+    grace_period: Duration,
+    /// Since when the number of peers has been below the floor.
+    below_since:  Option<Instant>,
+    /// Whether the baker was stopped due to the lack of peers.
+    stopped:      bool,
+}
+
+impl BakerPeerGuard {
+    pub fn new(min_peers: usize, grace_period: Duration) -> Self {
+        Self {
+            min_peers,
+            grace_period,
+            below_since: None,
+            stopped: false,
+        }
+    }
+
+    /// Register the current number of node peers and whether the node is
+    /// baking, returning the transition to apply to the baker, if any. Only a
+    /// baker stopped by the guard is restarted by it.
+    pub fn update(
+        &mut self,
+        peers: usize,
+        is_baking: bool,
+        now: Instant,
+    ) -> Option<BakerTransition> {
+        if peers >= self.min_peers {
+            self.below_since = None;
+            if self.stopped {
+                self.stopped = false;
+                return Some(BakerTransition::Start);
+            }
+        } else {
+            let below_since = *self.below_since.get_or_insert(now);
+            if is_baking && now.duration_since(below_since) >= self.grace_period {
+                self.stopped = true;
+                return Some(BakerTransition::Stop);
+            }
+        }
+        None
+    }
+}
+
+/// Stop or restart the baker depending on the number of node peers.
+pub fn check_baker_peers(
+    peer_stats: &[PeerStats],
+    consensus: &ConsensusContainer,
+    guard: &mut BakerPeerGuard,
+) {
+    let peers = peer_stats.iter().filter(|peer| peer.peer_type == PeerType::Node).count();
+    match guard.update(peers, consensus.is_baking(), Instant::now()) {
+        Some(BakerTransition::Stop) => {
+            warn!("Only {} node peers; stopping baking until more peers are connected", peers);
+            consensus.stop_baker();
+        }
+        Some(BakerTransition::Start) => {
+            info!("The number of node peers has recovered; resuming baking");
+            consensus.start_baker();
+        }
+        None => {}
+    }
+}
+
+/// Refresh the baking committee status reported by the health endpoint.
+pub fn update_baking_committee(node: &P2PNode, consensus: &ConsensusContainer) {
+    let (status, ..) = consensus.in_baking_committee();
+    *write_or_die!(node.stats.baking_committee) = Some(status as i64);
+}
+
 /// Check whether the peers require catching up.
 pub fn check_peer_states(node: &P2PNode, consensus: &ConsensusContainer) {
     // If we have a new genesis block, then mark all peers as pending.
@@ -553,3 +700,124 @@ fn update_peer_states(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        await_condition, await_handshakes, connect, get_test_config, make_node_and_sync,
+        make_node_and_sync_with_config, next_available_port, stop_node_delete_dirs,
+    };
+    use std::thread;
+
+    #[test]
+    fn genesis_hash_is_checked() {
+        let genesis_blocks = [BlockHash::new([1u8; 32]), BlockHash::new([2u8; 32])];
+
+        assert!(check_genesis_hash(&genesis_blocks, None).is_ok());
+        assert!(check_genesis_hash(&genesis_blocks, Some(&BlockHash::new([1u8; 32]))).is_ok());
+        // only the original genesis counts, not the regenesis blocks
+        assert!(check_genesis_hash(&genesis_blocks, Some(&BlockHash::new([2u8; 32]))).is_err());
+        assert!(check_genesis_hash(&[], Some(&BlockHash::new([1u8; 32]))).is_err());
+    }
+
+    #[test]
+    fn baker_follows_peer_count() {
+        let grace_period = Duration::from_secs(10);
+        let mut guard = BakerPeerGuard::new(2, grace_period);
+        let start = Instant::now();
+
+        assert_eq!(guard.update(3, true, start), None);
+        // the peers drop, but baking only stops after the grace period
+        assert_eq!(guard.update(1, true, start), None);
+        assert_eq!(guard.update(1, true, start + grace_period / 2), None);
+        assert_eq!(guard.update(1, true, start + grace_period), Some(BakerTransition::Stop));
+        assert_eq!(guard.update(0, false, start + 2 * grace_period), None);
+        // and resumes as soon as they recover
+        assert_eq!(guard.update(2, false, start + 3 * grace_period), Some(BakerTransition::Start));
+        assert_eq!(guard.update(2, true, start + 4 * grace_period), None);
+
+        // a short drop restarts the grace period
+        let later = start + 5 * grace_period;
+        assert_eq!(guard.update(1, true, later), None);
+        assert_eq!(guard.update(2, true, later + grace_period / 2), None);
+        assert_eq!(guard.update(1, true, later + grace_period), None);
+    }
+
+    #[test]
+    fn invalid_messages_quarantine() -> anyhow::Result<()> {
+        let mut config = get_test_config(next_available_port(), vec![100]);
+        config.connection.max_invalid_messages = Some(3);
+        let (node_1, dp_1) = make_node_and_sync_with_config(config, PeerType::Node, vec![])?;
+        let (node_2, dp_2) =
+            make_node_and_sync(next_available_port(), vec![100], PeerType::Node, vec![])?;
+        connect(&node_1, &node_2);
+        await_handshakes(&node_1);
+        await_handshakes(&node_2);
+        let peer_id = node_1.get_peer_stats(None)[0].local_id;
+
+        // a block from the peer that consensus fails to deserialize
+        let block = ConsensusMessage::new(
+            MessageType::Inbound(peer_id, DistributionMode::Broadcast),
+            Block,
+            Arc::from(&[0, 0, 0, 0, 0, 0xff][..]),
+            vec![peer_id],
+            None,
+        );
+        let reject = || {
+            register_consensus_result(
+                &node_1,
+                peer_id,
+                &block,
+                ConsensusFfiResponse::DeserializationError,
+            )
+        };
+
+        // messages consensus accepts don't count
+        register_consensus_result(&node_1, peer_id, &block, ConsensusFfiResponse::Success);
+        register_consensus_result(&node_1, peer_id, &block, ConsensusFfiResponse::DuplicateEntry);
+        // and up to the threshold the peer is tolerated
+        for _ in 0..3 {
+            reject();
+        }
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(node_1.get_peer_stats(None).len(), 1);
+
+        // but once it is exceeded it is disconnected and soft-banned
+        reject();
+        await_condition(Duration::from_secs(5), "the quarantine", || {
+            node_1.get_peer_stats(None).is_empty()
+        });
+        assert!(node_1.connection_handler.is_soft_banned(node_2.self_peer.addr));
+
+        stop_node_delete_dirs(dp_1, node_1);
+        stop_node_delete_dirs(dp_2, node_2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn baker_not_started_if_not_stopped_by_guard() {
+        let mut guard = BakerPeerGuard::new(2, Duration::from_secs(0));
+        let now = Instant::now();
+        assert_eq!(guard.update(0, false, now), None);
+        assert_eq!(guard.update(5, false, now), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn baker_credentials_permissions_are_checked() {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baker-credentials.json");
+        fs::write(&path, b"{}").unwrap();
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(check_baker_credentials_permissions(&path, true).is_ok());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(check_baker_credentials_permissions(&path, false).is_ok());
+        assert!(check_baker_credentials_permissions(&path, true).is_err());
+    }
+}
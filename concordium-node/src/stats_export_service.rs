@@ -2,7 +2,7 @@
 
 use crate::{
     common::p2p_node_id::P2PNodeId, configuration, consensus_ffi::consensus::ConsensusContainer,
-    read_or_die, spawn_or_die,
+    lock_or_die, read_or_die, spawn_or_die, write_or_die,
 };
 use anyhow::Context;
 use gotham::{
@@ -18,12 +18,12 @@ use hyper::Body;
 use prometheus::{
     self,
     core::{Atomic, AtomicI64, AtomicU64, GenericGauge},
-    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
-    Opts, Registry, TextEncoder,
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
 };
 use std::{
     net::SocketAddr,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     thread, time,
 };
 use tower_http::metrics::in_flight_requests::InFlightRequestsCounter;
@@ -38,13 +38,19 @@ impl IntoResponse for HTMLStringResponse {
 
 #[derive(Clone, gotham_derive::StateData)]
 struct PrometheusStateData {
-    registry: Arc<RwLock<Registry>>,
+    registry:         Arc<RwLock<Registry>>,
+    node_state:       Arc<Mutex<NodeState>>,
+    connected_peers:  IntGauge,
+    baking_committee: Arc<RwLock<Option<i64>>>,
 }
 
 impl PrometheusStateData {
-    fn new(registry: Registry) -> Self {
+    fn new(service: &StatsExportService) -> Self {
         Self {
-            registry: Arc::new(RwLock::new(registry)),
+            registry:         Arc::new(RwLock::new(service.registry.clone())),
+            node_state:       Arc::clone(&service.lifecycle),
+            connected_peers:  service.connected_peers.clone(),
+            baking_committee: Arc::clone(&service.baking_committee),
         }
     }
 }
@@ -85,10 +91,19 @@ pub struct StatsConsensusCollector {
     baking_lottery_power:       Gauge,
     /// The current number of non-finalized transactions across all accounts.
     non_finalized_transactions: IntGauge,
+    /// The last collected baking committee status, shared with the health
+    /// endpoint.
+    baking_committee_status:    Arc<RwLock<Option<i64>>>,
 }
 
 impl StatsConsensusCollector {
-    pub fn new(consensus: ConsensusContainer) -> anyhow::Result<Self> {
+    /// Create the collector, recording the baking committee status in the
+    /// given `baking_committee_status`; see
+    /// [`StatsExportService::baking_committee`].
+    pub fn new(
+        consensus: ConsensusContainer,
+        baking_committee_status: Arc<RwLock<Option<i64>>>,
+    ) -> anyhow::Result<Self> {
         let baking_committee = IntGauge::with_opts(Opts::new(
             "consensus_baking_committee",
             "The baking committee status of the node for the current best block. The value is \
@@ -112,12 +127,16 @@ impl StatsConsensusCollector {
             "The current number of non-finalized transactions across all accounts",
         ))?;
 
+        let (status, ..) = consensus.in_baking_committee();
+        *write_or_die!(baking_committee_status) = Some(status as i64);
+
         Ok(Self {
             consensus,
             baking_committee,
             finalization_committee,
             baking_lottery_power,
             non_finalized_transactions,
+            baking_committee_status,
         })
     }
 }
@@ -135,6 +154,7 @@ impl prometheus::core::Collector for StatsConsensusCollector {
         let (status, _has_baker_id, _baker_id, lottery_power) =
             self.consensus.in_baking_committee();
         self.baking_committee.set(status as i64);
+        *write_or_die!(self.baking_committee_status) = Some(status as i64);
         self.baking_lottery_power.set(lottery_power);
 
         let in_finalization_committee = self.consensus.in_finalization_committee();
@@ -157,6 +177,35 @@ impl prometheus::core::Collector for StatsConsensusCollector {
     }
 }
 
+/// The stages of the node's lifecycle, as reported by the `node_state` gauge.
+/// The node only ever moves to a later stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeState {
+    /// The node is being set up.
+    Starting      = 0,
+    /// The node is running, but hasn't completed a handshake with any peer yet.
+    Bootstrapping = 1,
+    /// The node has completed a handshake with a peer.
+    Ready         = 2,
+    /// The node is draining its connections before shutting down.
+    Draining      = 3,
+    /// The node is shutting down.
+    Stopping      = 4,
+}
+
+impl NodeState {
+    /// The status reported by the health endpoint in this stage.
+    fn health_status(self) -> &'static str {
+        match self {
+            // the node isn't connected to the network until it has bootstrapped
+            NodeState::Starting | NodeState::Bootstrapping => "starting",
+            NodeState::Ready => "ready",
+            NodeState::Draining => "draining",
+            NodeState::Stopping => "stopping",
+        }
+    }
+}
+
 /// Collects statistics pertaining to the node.
 pub struct StatsExportService {
     /// The Prometheus registry. Every metric which should be exposed via the
@@ -166,6 +215,12 @@ pub struct StatsExportService {
     pub packets_received: IntCounter,
     /// Total number of network packets sent.
     pub packets_sent: IntCounter,
+    /// Total number of received packets carrying consensus messages, labelled
+    /// by their destination (direct or broadcast).
+    pub received_network_packets: IntCounterVec,
+    /// Total number of sent packets carrying consensus messages, labelled by
+    /// their destination (direct or broadcast).
+    pub sent_network_packets: IntCounterVec,
     /// Current number of connected peers.
     pub connected_peers: IntGauge,
     /// Total number of connections received.
@@ -180,6 +235,9 @@ pub struct StatsExportService {
     pub outbound_low_priority_message_queue_size: IntGauge,
     /// Total number of bytes received.
     pub received_bytes: IntCounter,
+    /// Total number of connections dropped because the peer announced a
+    /// zero-sized message.
+    pub zero_sized_messages: IntCounter,
     /// Total number of bytes sent.
     pub sent_bytes: IntCounter,
     /// The block height of the last finalized block.
@@ -220,12 +278,56 @@ pub struct StatsExportService {
     /// - `"finalization message"`
     /// - `"catch-up status message"`
     pub sent_consensus_messages: IntCounterVec,
+    /// Histogram of the time spent in calls into the consensus layer. Labelled
+    /// with the operation (`operation=<name>`), which is the name of the
+    /// `ConsensusContainer` method making the call, e.g. `"receive_block"`.
+    pub consensus_call_duration: HistogramVec,
     /// Current number of soft banned peers.
     pub soft_banned_peers: IntGauge,
     /// The total number of soft banned peers since startup.
     pub soft_banned_peers_total: IntCounter,
     /// Total number of peers connected since startup.
     pub total_peers: IntCounter,
+    /// Total number of messages from peers that were dropped as unwanted.
+    /// Labelled with the reason (`reason=<reason>`).
+    ///
+    /// Possible values of `reason` are:
+    /// - `"before handshake"` A message other than the handshake was received
+    ///   on a connection that has not completed the handshake. The connection
+    ///   is closed.
+    /// - `"packet to bootstrapper"` A bootstrapper received a packet, which it
+    ///   does not process.
+    /// - `"stale broadcast"` A broadcast packet arrived later than allowed by
+    ///   the configured maximum broadcast age.
+    /// - `"untracked network"` A bootstrapper received a request to join a
+    ///   network it does not keep track of.
+    pub unwanted_messages: IntCounterVec,
+    /// Total number of handshakes rejected because the peer is on a different
+    /// chain.
+    pub wrong_genesis_peers: IntCounter,
+    /// Whether the node is draining its connections (1) or not (0).
+    pub draining: IntGauge,
+    /// The stage of the node's lifecycle; see [`NodeState`] for the encoding.
+    /// It is only updated via `advance_node_state`.
+    pub node_state: IntGauge,
+    /// The current stage of the node's lifecycle. The lock is held while the
+    /// `node_state` gauge is updated, so that concurrent updates can't move
+    /// it to an earlier stage.
+    lifecycle: Arc<Mutex<NodeState>>,
+    /// The baking committee status of the node as mapped by the
+    /// `consensus_baking_committee` gauge, as of the last housekeeping round
+    /// or collection of the consensus metrics. `None` while consensus has not
+    /// started. This is kept so that the health endpoint doesn't have to query
+    /// consensus.
+    pub baking_committee: Arc<RwLock<Option<i64>>>,
+    /// Total number of broadcasts dropped due to the broadcast rate limit.
+    pub broadcasts_throttled: IntCounter,
+    /// Total number of broadcast deliveries skipped because the peer had too
+    /// many bytes waiting to be sent to it.
+    pub broadcast_deliveries_skipped: IntCounter,
+    /// Histogram of the time between a peer creating a message and the node
+    /// receiving it.
+    pub message_transit_time: Histogram,
     /// Information of the node software. Contains a label `version` with the
     /// version of the node.
     pub node_info: IntGauge,
@@ -275,6 +377,43 @@ pub struct StatsExportService {
     /// The number of peers that recently connected to the node labelled by the
     /// bucket in which they are contained.
     pub peer_bucket_size: IntGaugeVec,
+    /// The push gateway the metrics are pushed to, if it is configured.
+    push_gateway: RwLock<Option<Arc<PushGateway>>>,
+}
+
+/// A Prometheus push gateway along with the parameters of the pushes.
+struct PushGateway {
+    registry:      Registry,
+    address:       String,
+    job_name:      String,
+    instance_name: String,
+    username:      Option<String>,
+    password:      Option<String>,
+    /// Held while pushing, so that concurrent pushes don't overlap.
+    push_lock:     Mutex<()>,
+}
+
+impl PushGateway {
+    /// Pushes the current values of the metrics to the gateway.
+    fn push(&self) -> anyhow::Result<()> {
+        let _guard = lock_or_die!(self.push_lock);
+        let username_pass = self.username.clone().and_then(|username| {
+            self.password.clone().map(|password| prometheus::BasicAuthentication {
+                username,
+                password,
+            })
+        });
+        prometheus::push_metrics(
+            &self.job_name,
+            prometheus::labels! {
+                "instance".to_owned() => self.instance_name.clone(),
+            },
+            &self.address,
+            self.registry.gather(),
+            username_pass,
+        )
+        .context("Can't push to prometheus push gateway")
+    }
 }
 
 impl StatsExportService {
@@ -294,6 +433,28 @@ impl StatsExportService {
         ))?;
         registry.register(Box::new(packets_sent.clone()))?;
 
+        let received_network_packets = IntCounterVec::new(
+            Opts::new(
+                "network_received_packet_messages_total",
+                "Total number of received packets carrying consensus messages labelled by their \
+                 destination",
+            )
+            .variable_label("destination"),
+            &["destination"],
+        )?;
+        registry.register(Box::new(received_network_packets.clone()))?;
+
+        let sent_network_packets = IntCounterVec::new(
+            Opts::new(
+                "network_sent_packet_messages_total",
+                "Total number of sent packets carrying consensus messages labelled by their \
+                 destination",
+            )
+            .variable_label("destination"),
+            &["destination"],
+        )?;
+        registry.register(Box::new(sent_network_packets.clone()))?;
+
         let connected_peers = IntGauge::with_opts(Opts::new(
             "network_connected_peers",
             "Current number of connected peers",
@@ -336,6 +497,12 @@ impl StatsExportService {
         ))?;
         registry.register(Box::new(received_bytes.clone()))?;
 
+        let zero_sized_messages = IntCounter::with_opts(Opts::new(
+            "network_zero_sized_messages_total",
+            "Total number of connections dropped because the peer announced a zero-sized message",
+        ))?;
+        registry.register(Box::new(zero_sized_messages.clone()))?;
+
         let sent_bytes =
             IntCounter::with_opts(Opts::new("network_sent_bytes", "Total number of bytes sent"))?;
         registry.register(Box::new(sent_bytes.clone()))?;
@@ -398,6 +565,18 @@ impl StatsExportService {
         )?;
         registry.register(Box::new(sent_consensus_messages.clone()))?;
 
+        let consensus_call_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "consensus_call_duration_seconds",
+                "Time spent in calls into the consensus layer in seconds, labelled by the \
+                 operation",
+            )
+            .variable_label("operation")
+            .buckets(vec![0.001, 0.005, 0.010, 0.050, 0.100, 0.500, 1.000, 5.000]),
+            &["operation"],
+        )?;
+        registry.register(Box::new(consensus_call_duration.clone()))?;
+
         let soft_banned_peers = IntGauge::with_opts(Opts::new(
             "network_soft_banned_peers",
             "Current number of soft banned peers",
@@ -416,6 +595,57 @@ impl StatsExportService {
         ))?;
         registry.register(Box::new(total_peers.clone()))?;
 
+        let unwanted_messages = IntCounterVec::new(
+            Opts::new(
+                "network_unwanted_messages_total",
+                "Total number of messages from peers that were dropped as unwanted, labelled by \
+                 the reason",
+            )
+            .variable_label("reason"),
+            &["reason"],
+        )?;
+        registry.register(Box::new(unwanted_messages.clone()))?;
+
+        let wrong_genesis_peers = IntCounter::with_opts(Opts::new(
+            "network_wrong_genesis_peers_total",
+            "Total number of handshakes rejected because the peer had different genesis blocks",
+        ))?;
+        registry.register(Box::new(wrong_genesis_peers.clone()))?;
+
+        let draining = IntGauge::with_opts(Opts::new(
+            "network_draining",
+            "Whether the node is draining its connections before shutting down (1) or not (0)",
+        ))?;
+        registry.register(Box::new(draining.clone()))?;
+
+        let node_state = IntGauge::with_opts(Opts::new(
+            "node_state",
+            "The stage of the node's lifecycle: starting (0), bootstrapping (1), ready (2), \
+             draining (3) or stopping (4)",
+        ))?;
+        registry.register(Box::new(node_state.clone()))?;
+
+        let broadcasts_throttled = IntCounter::with_opts(Opts::new(
+            "network_broadcasts_throttled_total",
+            "Total number of broadcasts dropped due to the broadcast rate limit",
+        ))?;
+        registry.register(Box::new(broadcasts_throttled.clone()))?;
+
+        let broadcast_deliveries_skipped = IntCounter::with_opts(Opts::new(
+            "network_broadcast_deliveries_skipped_total",
+            "Total number of broadcast deliveries skipped because the peer was saturated",
+        ))?;
+        registry.register(Box::new(broadcast_deliveries_skipped.clone()))?;
+
+        let message_transit_time = Histogram::with_opts(
+            HistogramOpts::new(
+                "network_message_transit_time_seconds",
+                "Time between a peer creating a network message and its receipt in seconds",
+            )
+            .buckets(vec![0.010, 0.050, 0.100, 0.250, 0.500, 1.000, 5.000]),
+        )?;
+        registry.register(Box::new(message_transit_time.clone()))?;
+
         let node_info = IntGauge::with_opts(
             Opts::new(
                 "node_info",
@@ -485,6 +715,8 @@ impl StatsExportService {
             registry,
             packets_received,
             packets_sent,
+            received_network_packets,
+            sent_network_packets,
             connected_peers,
             connections_received,
             inbound_high_priority_message_queue_size,
@@ -492,6 +724,7 @@ impl StatsExportService {
             outbound_high_priority_message_queue_size,
             outbound_low_priority_message_queue_size,
             received_bytes,
+            zero_sized_messages,
             sent_bytes,
             last_finalized_block_height,
             last_finalized_block_timestamp,
@@ -501,9 +734,19 @@ impl StatsExportService {
             finalized_baked_blocks,
             received_consensus_messages,
             sent_consensus_messages,
+            consensus_call_duration,
             soft_banned_peers,
             soft_banned_peers_total,
             total_peers,
+            unwanted_messages,
+            wrong_genesis_peers,
+            draining,
+            node_state,
+            lifecycle: Arc::new(Mutex::new(NodeState::Starting)),
+            baking_committee: Default::default(),
+            broadcasts_throttled,
+            broadcast_deliveries_skipped,
+            message_transit_time,
             node_info,
             node_startup_timestamp,
             grpc_request_response_time,
@@ -515,6 +758,7 @@ impl StatsExportService {
             avg_bps_in,
             avg_bps_out,
             peer_bucket_size,
+            push_gateway: RwLock::new(None),
         })
     }
 
@@ -539,14 +783,39 @@ impl StatsExportService {
         (state, message)
     }
 
+    /// Report whether the node is ready, along with its number of peers and
+    /// its baking committee status, if known. Load balancers can use this as
+    /// the node is only reported healthy (200) once it has completed a
+    /// handshake with a peer and until it starts draining its connections or
+    /// shutting down.
+    fn health(state: State) -> (State, Response<Body>) {
+        let state_data = PrometheusStateData::borrow_from(&state);
+        let status = lock_or_die!(state_data.node_state).health_status();
+        // the baking committee status is only known once consensus has started
+        let baking_committee = *read_or_die!(state_data.baking_committee);
+        let body = serde_json::json!({
+            "status": status,
+            "peers": state_data.connected_peers.get(),
+            "bakingCommittee": baking_committee,
+        });
+        let code = if status == "ready" {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        let response = create_response(&state, code, mime::APPLICATION_JSON, body.to_string());
+        (state, response)
+    }
+
     fn router(&self) -> Router {
-        let state_data = PrometheusStateData::new(self.registry.clone());
+        let state_data = PrometheusStateData::new(self);
         let middleware = StateMiddleware::new(state_data);
         let pipeline = single_middleware(middleware);
         let (chain, pipelines) = single_pipeline(pipeline);
         build_router(chain, pipelines, |route| {
             route.get("/").to(Self::index);
             route.get("/metrics").to(Self::metrics);
+            route.get("/health").to(Self::health);
         })
     }
 
@@ -577,29 +846,57 @@ impl StatsExportService {
         prometheus_push_username: Option<String>,
         prometheus_push_password: Option<String>,
     ) {
-        let metrics_families = self.registry.gather();
+        let gateway = Arc::new(PushGateway {
+            registry:      self.registry.clone(),
+            address:       prometheus_push_gateway,
+            job_name:      prometheus_job_name,
+            instance_name: prometheus_instance_name,
+            username:      prometheus_push_username,
+            password:      prometheus_push_password,
+            push_lock:     Mutex::new(()),
+        });
+        *write_or_die!(self.push_gateway) = Some(Arc::clone(&gateway));
         let _th = spawn_or_die!("Prometheus", move || loop {
-            debug!("Pushing data to push gateway");
-            let username_pass = prometheus_push_username.clone().and_then(|username| {
-                prometheus_push_password.clone().map(|password| prometheus::BasicAuthentication {
-                    username,
-                    password,
-                })
-            });
             thread::sleep(time::Duration::from_secs(prometheus_push_interval));
-            prometheus::push_metrics(
-                &prometheus_job_name,
-                prometheus::labels! {
-                    "instance".to_owned() => prometheus_instance_name.clone(),
-                },
-                &prometheus_push_gateway,
-                metrics_families.clone(),
-                username_pass,
-            )
-            .map_err(|e| error!("Can't push to prometheus push gateway {}", e))
-            .ok();
+            debug!("Pushing data to push gateway");
+            gateway.push().map_err(|e| error!("{:#}", e)).ok();
         });
     }
+
+    /// Move the node to the given stage of its lifecycle, unless it has already
+    /// reached a later one.
+    pub fn advance_node_state(&self, state: NodeState) {
+        let mut lifecycle = lock_or_die!(self.lifecycle);
+        if *lifecycle < state {
+            *lifecycle = state;
+            self.node_state.set(state as i64);
+        }
+    }
+
+    /// Immediately pushes the current metrics to the push gateway, e.g. so that
+    /// the last datapoints are not lost when the node shuts down. This does
+    /// nothing if no push gateway is configured.
+    pub fn flush_now(&self) -> anyhow::Result<()> {
+        let gateway = read_or_die!(self.push_gateway).clone();
+        if let Some(gateway) = gateway {
+            debug!("Flushing data to push gateway");
+            gateway.push()?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the given call into the consensus layer, recording its duration in
+/// `call_duration` under the given operation.
+pub fn time_consensus_call<T>(
+    call_duration: &HistogramVec,
+    operation: &str,
+    call: impl FnOnce() -> T,
+) -> T {
+    let timer = call_duration.with_label_values(&[operation]).start_timer();
+    let result = call();
+    timer.observe_duration();
+    result
 }
 
 /// Starts the stats export engine.
@@ -635,3 +932,178 @@ pub fn start_push_gateway(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::await_condition;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    /// Requests the health endpoint, returning the status code and the body.
+    fn get_health(addr: SocketAddr) -> (u16, serde_json::Value) {
+        let mut stream = None;
+        await_condition(time::Duration::from_secs(5), "the server to start", || {
+            stream = std::net::TcpStream::connect(addr).ok();
+            stream.is_some()
+        });
+        let mut stream = stream.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let code = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        (code, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn health_reflects_node_state() {
+        let service = Arc::new(StatsExportService::new(vec![]).unwrap());
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (error_sender, _error_receiver) = tokio::sync::broadcast::channel(1);
+        let server = Arc::clone(&service);
+        runtime.spawn(async move { server.start_server(addr, error_sender).await });
+
+        let (code, body) = get_health(addr);
+        assert_eq!(code, 503);
+        assert_eq!(body["status"], "starting");
+
+        service.advance_node_state(NodeState::Bootstrapping);
+        let (code, body) = get_health(addr);
+        assert_eq!(code, 503);
+        assert_eq!(body["status"], "starting");
+
+        service.advance_node_state(NodeState::Ready);
+        service.connected_peers.set(3);
+        let (code, body) = get_health(addr);
+        assert_eq!(code, 200);
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["peers"], 3);
+        assert!(body["bakingCommittee"].is_null());
+
+        *write_or_die!(service.baking_committee) = Some(0);
+        let (_, body) = get_health(addr);
+        assert_eq!(body["bakingCommittee"], 0);
+
+        service.advance_node_state(NodeState::Draining);
+        let (code, body) = get_health(addr);
+        assert_eq!(code, 503);
+        assert_eq!(body["status"], "draining");
+
+        service.advance_node_state(NodeState::Stopping);
+        let (code, body) = get_health(addr);
+        assert_eq!(code, 503);
+        assert_eq!(body["status"], "stopping");
+    }
+
+    #[test]
+    fn node_state_only_advances() {
+        let service = StatsExportService::new(vec![]).unwrap();
+        assert_eq!(service.node_state.get(), NodeState::Starting as i64);
+
+        service.advance_node_state(NodeState::Bootstrapping);
+        assert_eq!(service.node_state.get(), NodeState::Bootstrapping as i64);
+        service.advance_node_state(NodeState::Draining);
+        assert_eq!(service.node_state.get(), NodeState::Draining as i64);
+        // e.g. a handshake completed while draining
+        service.advance_node_state(NodeState::Ready);
+        assert_eq!(service.node_state.get(), NodeState::Draining as i64);
+    }
+
+    #[test]
+    fn concurrent_node_state_updates_dont_regress() {
+        let service = Arc::new(StatsExportService::new(vec![]).unwrap());
+        let threads = (0..8)
+            .map(|i| {
+                let service = Arc::clone(&service);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        // e.g. handshakes completing while the node starts shutting down
+                        if i == 0 {
+                            service.advance_node_state(NodeState::Stopping);
+                        } else {
+                            service.advance_node_state(NodeState::Ready);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(service.node_state.get(), NodeState::Stopping as i64);
+    }
+
+    #[test]
+    fn consensus_calls_are_timed() {
+        let service = StatsExportService::new(vec![]).unwrap();
+
+        let call_duration = &service.consensus_call_duration;
+        assert_eq!(time_consensus_call(call_duration, "receive_block", || 1), 1);
+        time_consensus_call(call_duration, "receive_block", || {
+            thread::sleep(time::Duration::from_millis(10))
+        });
+        time_consensus_call(call_duration, "send_transaction", || ());
+
+        let block = service.consensus_call_duration.with_label_values(&["receive_block"]);
+        assert_eq!(block.get_sample_count(), 2);
+        assert!(block.get_sample_sum() >= 0.01);
+        let transaction = service.consensus_call_duration.with_label_values(&["send_transaction"]);
+        assert_eq!(transaction.get_sample_count(), 1);
+    }
+
+    /// Accepts a single HTTP request and returns its body.
+    fn receive_push(listener: TcpListener) -> Vec<u8> {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        body
+    }
+
+    #[test]
+    fn flush_pushes_current_metrics() {
+        let service = StatsExportService::new(vec![]).unwrap();
+        // nothing to flush to
+        assert!(service.flush_now().is_ok());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let gateway = thread::spawn(move || receive_push(listener));
+        // the interval is long enough for the periodic push not to interfere
+        service.start_push_to_gateway(
+            address,
+            3600,
+            "test-job".to_owned(),
+            "test-instance".to_owned(),
+            None,
+            None,
+        );
+        service.packets_received.inc_by(42);
+        service.flush_now().unwrap();
+
+        let body = gateway.join().unwrap();
+        let contains = |needle: &[u8]| body.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"network_packets_received_total"));
+        assert!(contains(&42f64.to_le_bytes()));
+    }
+}
@@ -24,7 +24,7 @@ use std::{
         Arc,
     },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 static PORT_OFFSET: AtomicUsize = AtomicUsize::new(0);
@@ -57,6 +57,7 @@ pub fn get_test_config(port: u16, networks: Vec<u16>) -> Config {
         100,
     );
     config.connection.no_bootstrap_dns = true;
+    config.connection.allow_local_peer_addresses = true;
     config.cli.no_network = true;
     let dir = td.into_path();
     config.common.data_dir = dir.clone();
@@ -102,9 +103,19 @@ pub fn make_node_and_sync(
     networks: Vec<u16>,
     node_type: PeerType,
     regenesis_blocks: Vec<BlockHash>,
+) -> anyhow::Result<(Arc<P2PNode>, DeletePermission)> {
+    make_node_and_sync_with_config(get_test_config(port, networks), node_type, regenesis_blocks)
+}
+
+/// Creates a `P2PNode` for test purposes using the given configuration, which
+/// should be obtained from `get_test_config`. Its directories are handled as
+/// in `make_node_and_sync`.
+pub fn make_node_and_sync_with_config(
+    mut config: Config,
+    node_type: PeerType,
+    regenesis_blocks: Vec<BlockHash>,
 ) -> anyhow::Result<(Arc<P2PNode>, DeletePermission)> {
     // locally-run tests and benches can be polled with a much greater frequency
-    let mut config = get_test_config(port, networks);
     config.cli.no_network = true;
     config.cli.poll_interval = 1;
     config.connection.housekeeping_interval = 10;
@@ -137,6 +148,58 @@ pub fn await_handshakes(node: &P2PNode) {
     }
 }
 
+/// Creates a cluster of `n` nodes in the given networks in which every node
+/// connects to all the nodes created before it. Its directories are handled as
+/// in `make_node_and_sync`.
+pub fn make_cluster(
+    n: usize,
+    networks: Vec<u16>,
+) -> anyhow::Result<Vec<(Arc<P2PNode>, DeletePermission)>> {
+    let mut nodes: Vec<(Arc<P2PNode>, DeletePermission)> = Vec::with_capacity(n);
+    for _ in 0..n {
+        let node = make_node_and_sync(
+            next_available_port(),
+            networks.clone(),
+            PeerType::Node,
+            dummy_regenesis_blocks(),
+        )?;
+        for (other, _) in &nodes {
+            connect(&node.0, other);
+        }
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+/// Waits until every node in the cluster has completed a handshake with all
+/// the other nodes.
+pub fn await_cluster_handshakes(nodes: &[(Arc<P2PNode>, DeletePermission)]) {
+    for (node, _) in nodes {
+        loop {
+            let peers = node.get_peer_stats(None);
+            if nodes
+                .iter()
+                .filter(|(other, _)| other.id() != node.id())
+                .all(|(other, _)| peers.iter().any(|peer| peer.self_id == other.id()))
+            {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Waits until `condition` holds, checking it every 10ms. Panics with a message
+/// saying what was awaited if it doesn't hold within `timeout`.
+pub fn await_condition(timeout: Duration, what: &str, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + timeout;
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
 /// Creates a vector of given size containing random bytes.
 pub fn generate_random_data(size: usize) -> Vec<u8> {
     thread_rng().sample_iter(&Alphanumeric).take(size).map(|c| c as u32 as u8).collect()
@@ -1,25 +1,182 @@
 //! Miscellaneous utilities.
 
-use crate::configuration as config;
+use crate::{configuration as config, lock_or_die};
+use anyhow::{bail, Context};
 use env_logger::{Builder, Env};
-use log::LevelFilter;
+use log::{LevelFilter, Metadata, Record};
+use log4rs::{
+    append::{
+        rolling_file::{
+            policy::compound::{
+                roll::fixed_window::FixedWindowRoller, trigger::Trigger, CompoundPolicy,
+            },
+            LogFile, RollingFileAppender,
+        },
+        Append,
+    },
+    encode::pattern::PatternEncoder,
+};
 use std::{
+    collections::{HashMap, HashSet},
     io::Write,
-    net::{SocketAddr, ToSocketAddrs},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
     path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 pub fn to_hex_string(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
+/// The destinations the node's logs can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTarget {
+    /// Standard error, the default.
+    Stderr,
+    /// A log file which is rotated based on its size and age.
+    File,
+    /// The local syslog daemon.
+    Syslog,
+}
+
+impl FromStr for LogTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        match target {
+            "stderr" => Ok(LogTarget::Stderr),
+            "file" => Ok(LogTarget::File),
+            "syslog" => Ok(LogTarget::Syslog),
+            _ => bail!("Could not parse log target"),
+        }
+    }
+}
+
+/// A range of IP addresses in CIDR notation, e.g. `10.0.0.0/8`. A plain
+/// address denotes a range containing just that address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    network:    IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Check whether the given address is in the range.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                prefix_matches(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                prefix_matches(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: &[u8], addr: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = usize::from(prefix_len / 8);
+    let rest_bits = prefix_len % 8;
+    network[..full_bytes] == addr[..full_bytes]
+        && (rest_bits == 0 || (network[full_bytes] ^ addr[full_bytes]) >> (8 - rest_bits) == 0)
+}
+
+impl FromStr for IpRange {
+    type Err = anyhow::Error;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let (network, prefix_len) = match range.split_once('/') {
+            Some((network, prefix_len)) => (network, Some(prefix_len)),
+            None => (range, None),
+        };
+        let network = IpAddr::from_str(network).context("Could not parse the IP address")?;
+        let max_prefix_len = if network.is_ipv4() {
+            32
+        } else {
+            128
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => {
+                u8::from_str(prefix_len).context("Could not parse the prefix length")?
+            }
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            bail!("The prefix length of {} is too long", range)
+        }
+        Ok(IpRange {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+/// Convert an IPv4-mapped IPv6 address (`::ffff:a.b.c.d`) to the IPv4 address
+/// it represents, so that a peer has a single address regardless of the form
+/// it was reached by. Other addresses are returned unchanged.
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => IpAddr::from([a, b, c, d]),
+            _ => ip,
+        },
+        _ => ip,
+    }
+}
+
+/// Convert the IP of a socket address to its canonical form; see
+/// [`canonical_ip`].
+pub fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(canonical_ip(addr.ip()), addr.port())
+}
+
+/// The subnet of the given address that limits on the number of peers per
+/// subnet apply to: its /24 for IPv4 and its /64 for IPv6 addresses.
+pub fn peer_subnet(ip: IpAddr) -> IpAddr {
+    match canonical_ip(ip) {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            IpAddr::from([a, b, c, 0])
+        }
+        IpAddr::V6(v6) => {
+            let mut octets = v6.octets();
+            octets[8..].iter_mut().for_each(|octet| *octet = 0);
+            IpAddr::from(octets)
+        }
+    }
+}
+
+/// Check whether the node may connect to the given address, as supplied by a
+/// peer. Unspecified and multicast addresses are never accepted, loopback and
+/// link-local ones only if `allow_local` is set, and addresses in any of the
+/// `blocked` ranges neither.
+pub fn is_acceptable_peer_ip(ip: IpAddr, allow_local: bool, blocked: &[IpRange]) -> bool {
+    // treat IPv4-mapped IPv6 addresses as the IPv4 addresses they represent
+    let ip = canonical_ip(ip);
+    let (is_invalid, is_local) = match ip {
+        IpAddr::V4(v4) => (
+            v4.is_unspecified() || v4.is_multicast() || v4.is_broadcast(),
+            v4.is_loopback() || v4.is_link_local(),
+        ),
+        IpAddr::V6(v6) => (
+            v6.is_unspecified() || v6.is_multicast(),
+            v6.is_loopback() || v6.segments()[0] & 0xffc0 == 0xfe80,
+        ),
+    };
+    !is_invalid && (allow_local || !is_local) && !blocked.iter().any(|range| range.contains(ip))
+}
+
 /// Setup a log4rs logger based on the given configuration file.
 pub fn setup_logger_config(config_file: &Path) {
     log4rs::init_file(config_file, Default::default()).unwrap();
 }
 
-/// Sets up a logger that logs to stderr.
-pub fn setup_logger(trace: bool, debug: bool, no_log_timestamp: bool) {
+/// Creates the logger builder shared by all the log targets, which determines
+/// the log level and silences the chattier dependencies.
+fn log_builder(trace: bool, debug: bool, no_log_timestamp: bool) -> Builder {
     let env = if trace {
         Env::default().filter_or("LOG_LEVEL", "trace")
     } else if debug {
@@ -41,7 +198,12 @@ pub fn setup_logger(trace: bool, debug: bool, no_log_timestamp: bool) {
     log_builder.filter(Some("reqwest"), LevelFilter::Error);
     log_builder.filter(Some("gotham"), LevelFilter::Error);
     log_builder.filter(Some("h2"), LevelFilter::Error);
-    log_builder.init();
+    log_builder
+}
+
+/// Sets up a logger that logs to stderr.
+pub fn setup_logger(trace: bool, debug: bool, no_log_timestamp: bool) {
+    log_builder(trace, debug, no_log_timestamp).init();
 }
 
 /// Sets up a logger for the macOS syslog which logs with the provided
@@ -69,21 +231,278 @@ pub fn setup_macos_logger(trace: bool, debug: bool, subsystem: &str) {
         .expect("Failed to initialise MacOsLogger");
 }
 
-pub fn get_bootstrap_nodes(bootstrap_nodes: &[String]) -> Result<Vec<SocketAddr>, String> {
-    if !bootstrap_nodes.is_empty() {
+/// A logger which filters records in the same way as the stderr logger, but
+/// hands them over to a log4rs appender for writing.
+struct AppenderLogger {
+    filter:   env_logger::Logger,
+    appender: Box<dyn Append>,
+}
+
+impl AppenderLogger {
+    fn new(filter: env_logger::Logger, appender: Box<dyn Append>) -> Self {
+        Self {
+            filter,
+            appender,
+        }
+    }
+
+    /// Installs the logger as the global logger.
+    fn init(self) {
+        log::set_max_level(self.filter.filter());
+        log::set_boxed_logger(Box::new(self)).expect("The logger has already been initialized");
+    }
+}
+
+impl log::Log for AppenderLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool { self.filter.enabled(metadata) }
+
+    fn log(&self, record: &Record) {
+        if self.filter.matches(record) {
+            if let Err(e) = self.appender.append(record) {
+                eprintln!("Could not write a log record: {}", e);
+            }
+        }
+    }
+
+    fn flush(&self) { self.appender.flush() }
+}
+
+/// Rolls the log file over once it has either grown past the maximum size or
+/// has been written to for longer than the maximum age. The age is measured
+/// from the last roll-over, or from startup if the file was never rolled.
+#[derive(Debug)]
+struct SizeOrAgeTrigger {
+    max_size:  u64,
+    max_age:   Option<Duration>,
+    last_roll: Mutex<Instant>,
+}
+
+impl Trigger for SizeOrAgeTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        let mut last_roll = lock_or_die!(self.last_roll);
+        let too_old = self.max_age.map_or(false, |max_age| last_roll.elapsed() >= max_age);
+        if file.len_estimate() > self.max_size || too_old {
+            *last_roll = Instant::now();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Creates an appender writing to the given file, keeping at most `keep`
+/// rotated files next to it, named `<path>.<n>`.
+fn rolling_file_appender(
+    path: &Path,
+    no_log_timestamp: bool,
+    max_size: u64,
+    max_age: Option<Duration>,
+    keep: u32,
+) -> anyhow::Result<RollingFileAppender> {
+    let trigger = SizeOrAgeTrigger {
+        max_size,
+        max_age,
+        last_roll: Mutex::new(Instant::now()),
+    };
+    let roller = FixedWindowRoller::builder()
+        .build(&format!("{}.{{}}", path.display()), keep)
+        .context("Invalid log file rotation settings")?;
+    let policy = CompoundPolicy::new(Box::new(trigger), Box::new(roller));
+    // Mirror the format used for logging to stderr.
+    let pattern = if no_log_timestamp {
+        "{l}: {m}{n}"
+    } else {
+        "{d(%Y-%m-%dT%H:%M:%S%.9fZ)(utc)}: {l}: {m}{n}"
+    };
+    RollingFileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(pattern)))
+        .build(path, Box::new(policy))
+        .with_context(|| format!("Could not open the log file {}", path.display()))
+}
+
+/// Sets up a logger that logs to a file, which is rotated once it exceeds
+/// `max_size` bytes or, if given, is older than `max_age`.
+pub fn setup_file_logger(
+    trace: bool,
+    debug: bool,
+    no_log_timestamp: bool,
+    path: &Path,
+    max_size: u64,
+    max_age: Option<Duration>,
+    keep: u32,
+) -> anyhow::Result<()> {
+    let appender = rolling_file_appender(path, no_log_timestamp, max_size, max_age, keep)?;
+    let filter = log_builder(trace, debug, no_log_timestamp).build();
+    AppenderLogger::new(filter, Box::new(appender)).init();
+    Ok(())
+}
+
+/// An appender forwarding log records to the local syslog daemon.
+#[cfg(unix)]
+#[derive(Debug)]
+struct SyslogAppender;
+
+#[cfg(unix)]
+impl SyslogAppender {
+    fn new() -> Self {
+        // A null identifier makes syslog use the name of the program.
+        unsafe { libc::openlog(std::ptr::null(), libc::LOG_PID, libc::LOG_DAEMON) };
+        SyslogAppender
+    }
+}
+
+#[cfg(unix)]
+impl Append for SyslogAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let priority = match record.level() {
+            log::Level::Error => libc::LOG_ERR,
+            log::Level::Warn => libc::LOG_WARNING,
+            log::Level::Info => libc::LOG_INFO,
+            log::Level::Debug | log::Level::Trace => libc::LOG_DEBUG,
+        };
+        let message = std::ffi::CString::new(record.args().to_string().replace('\0', " "))?;
+        unsafe {
+            libc::syslog(priority, b"%s\0".as_ptr() as *const libc::c_char, message.as_ptr())
+        };
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sets up a logger that logs to the local syslog daemon. Syslog adds its own
+/// timestamps and levels, so they are not included in the messages.
+#[cfg(unix)]
+pub fn setup_syslog_logger(trace: bool, debug: bool) -> anyhow::Result<()> {
+    let filter = log_builder(trace, debug, true).build();
+    AppenderLogger::new(filter, Box::new(SyslogAppender::new())).init();
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn setup_syslog_logger(_trace: bool, _debug: bool) -> anyhow::Result<()> {
+    bail!("Logging to syslog is only supported on unix platforms")
+}
+
+/// Sets up a logger for the target chosen in the configuration.
+fn setup_log_target(conf: &config::CommonConfig) -> anyhow::Result<()> {
+    match conf.log_target {
+        LogTarget::Stderr => {
+            setup_logger(conf.trace, conf.debug, conf.no_log_timestamp);
+            Ok(())
+        }
+        LogTarget::File => {
+            let path = conf.log_file.as_ref().context("No log file given")?;
+            setup_file_logger(
+                conf.trace,
+                conf.debug,
+                conf.no_log_timestamp,
+                path,
+                conf.log_file_max_size,
+                conf.log_file_max_age.map(Duration::from_secs),
+                conf.log_file_keep,
+            )
+        }
+        LogTarget::Syslog => setup_syslog_logger(conf.trace, conf.debug),
+    }
+}
+
+/// The maximum number of threads resolving the addresses of the bootstrappers.
+const MAX_BOOTSTRAP_RESOLVER_THREADS: usize = 4;
+
+/// The result of resolving the address of a bootstrapper.
+type Resolution = (String, std::io::Result<Vec<SocketAddr>>);
+
+/// Resolves the addresses of the bootstrappers on a bounded pool of
+/// long-lived threads. A bootstrapper whose previous lookup is still pending,
+/// e.g. due to a slow resolver, isn't looked up again until that lookup
+/// completes, so lookups that hang don't pile up.
+pub struct BootstrapResolver {
+    requests: crossbeam_channel::Sender<String>,
+    results:  crossbeam_channel::Receiver<Resolution>,
+    pending:  HashSet<String>,
+}
+
+impl BootstrapResolver {
+    /// Start a resolver with a thread for each of the given number of
+    /// bootstrappers, up to `MAX_BOOTSTRAP_RESOLVER_THREADS`.
+    pub fn new(bootstrappers: usize) -> std::io::Result<Self> {
+        Self::with_resolve(bootstrappers, |ip_port| {
+            ToSocketAddrs::to_socket_addrs(ip_port).map(Iterator::collect)
+        })
+    }
+
+    fn with_resolve(
+        bootstrappers: usize,
+        resolve: impl Fn(&str) -> std::io::Result<Vec<SocketAddr>> + Send + Sync + 'static,
+    ) -> std::io::Result<Self> {
+        let resolve = Arc::new(resolve);
+        let (requests, request_receiver) = crossbeam_channel::unbounded::<String>();
+        let (result_sender, results) = crossbeam_channel::unbounded();
+        for _ in 0..bootstrappers.clamp(1, MAX_BOOTSTRAP_RESOLVER_THREADS) {
+            let (resolve, request_receiver, result_sender) =
+                (Arc::clone(&resolve), request_receiver.clone(), result_sender.clone());
+            // the threads terminate once the resolver is dropped
+            std::thread::Builder::new().name("bootstrap resolver".to_owned()).spawn(move || {
+                for ip_port in request_receiver {
+                    let result = resolve(&ip_port);
+                    if result_sender.send((ip_port, result)).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        }
+        Ok(BootstrapResolver {
+            requests,
+            results,
+            pending: HashSet::new(),
+        })
+    }
+
+    /// Resolve the given bootstrappers, waiting for the results until the
+    /// `deadline`. The order of the bootstrappers is kept.
+    pub fn resolve(
+        &mut self,
+        bootstrap_nodes: &[String],
+        deadline: Instant,
+    ) -> Result<Vec<SocketAddr>, String> {
+        if bootstrap_nodes.is_empty() {
+            return Err("No bootstrap nodes specified.".to_string());
+        }
+
         debug!("Resolving bootstrapper nodes.");
-        let bootstrap_nodes = bootstrap_nodes
+        for ip_port in bootstrap_nodes {
+            if self.pending.insert(ip_port.clone()) {
+                // the threads only terminate once the resolver is dropped
+                let _ = self.requests.send(ip_port.clone());
+            } else {
+                warn!("Still waiting for the previous resolution of bootstrapper node {}", ip_port);
+            }
+        }
+
+        let mut resolved = HashMap::with_capacity(bootstrap_nodes.len());
+        while bootstrap_nodes.iter().any(|ip_port| self.pending.contains(ip_port)) {
+            let (ip_port, result) = match self.results.recv_deadline(deadline) {
+                Ok(resolution) => resolution,
+                Err(_) => break,
+            };
+            self.pending.remove(&ip_port);
+            match result {
+                Ok(addrs) => {
+                    resolved.insert(ip_port, addrs);
+                }
+                Err(err) => error!("Invalid bootstrapper node received: {}", err),
+            }
+        }
+        for ip_port in bootstrap_nodes.iter().filter(|ip_port| self.pending.contains(*ip_port)) {
+            warn!("Timed out resolving bootstrapper node {}", ip_port);
+        }
+
+        Ok(bootstrap_nodes
             .iter()
-            .filter_map(|ip_port| {
-                ToSocketAddrs::to_socket_addrs(ip_port)
-                    .map_err(|err| error!("Invalid bootstrapper node received: {}", err))
-                    .ok()
-            })
+            .filter_map(|ip_port| resolved.remove(ip_port))
             .flatten()
-            .collect::<Vec<_>>();
-        Ok(bootstrap_nodes)
-    } else {
-        Err("No bootstrap nodes specified.".to_string())
+            .collect())
     }
 }
 
@@ -110,14 +529,14 @@ pub fn get_config_and_logging_setup() -> anyhow::Result<(config::Config, config:
     #[cfg(target_os = "macos")]
     match conf.macos.use_mac_log {
         Some(ref subsystem) => setup_macos_logger(conf.common.trace, conf.common.debug, subsystem),
-        None => setup_logger(conf.common.trace, conf.common.debug, conf.common.no_log_timestamp),
+        None => setup_log_target(&conf.common)?,
     };
 
     #[cfg(not(target_os = "macos"))]
     if let Some(ref log_config) = conf.common.log_config {
         setup_logger_config(log_config);
     } else {
-        setup_logger(conf.common.trace, conf.common.debug, conf.common.no_log_timestamp);
+        setup_log_target(&conf.common)?;
     }
 
     info!("Starting up {} version {}!", crate::APPNAME, crate::VERSION);
@@ -142,3 +561,151 @@ pub fn get_config_and_logging_setup() -> anyhow::Result<(config::Config, config:
 
     Ok((conf, app_prefs))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn ip_ranges_are_parsed() {
+        let range: IpRange = "10.0.0.0/8".parse().unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.1.2.3".parse().unwrap()));
+        assert!(!range.contains("::a01:203".parse().unwrap()));
+
+        let range: IpRange = "172.16.0.0/12".parse().unwrap();
+        assert!(range.contains("172.31.255.255".parse().unwrap()));
+        assert!(!range.contains("172.32.0.0".parse().unwrap()));
+
+        let range: IpRange = "fc00::/7".parse().unwrap();
+        assert!(range.contains("fd12::1".parse().unwrap()));
+        assert!(!range.contains("fe80::1".parse().unwrap()));
+
+        let range: IpRange = "192.168.1.1".parse().unwrap();
+        assert!(range.contains("192.168.1.1".parse().unwrap()));
+        assert!(!range.contains("192.168.1.2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("10.0.0/8".parse::<IpRange>().is_err());
+        assert!("10.0.0.0/x".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn mapped_addresses_are_canonicalized() {
+        let canonical = |addr: &str| canonical_addr(addr.parse().unwrap()).to_string();
+
+        assert_eq!(canonical("[::ffff:10.1.2.3]:8888"), "10.1.2.3:8888");
+        assert_eq!(canonical("10.1.2.3:8888"), "10.1.2.3:8888");
+        // IPv4-compatible and other IPv6 addresses are left alone
+        assert_eq!(canonical("[::1]:8888"), "[::1]:8888");
+        assert_eq!(canonical("[::a01:203]:8888"), "[::a01:203]:8888");
+        assert_eq!(canonical("[2001:db8::1]:8888"), "[2001:db8::1]:8888");
+    }
+
+    #[test]
+    fn peer_subnets() {
+        let subnet = |ip: &str| peer_subnet(ip.parse().unwrap()).to_string();
+
+        assert_eq!(subnet("10.1.2.3"), "10.1.2.0");
+        assert_eq!(subnet("::ffff:10.1.2.3"), "10.1.2.0");
+        assert_eq!(subnet("2001:db8:1:2:3:4:5:6"), "2001:db8:1:2::");
+    }
+
+    #[test]
+    fn peer_ips_are_filtered() {
+        let blocked = ["10.0.0.0/8".parse().unwrap()];
+        let acceptable = |ip: &str, allow_local| {
+            is_acceptable_peer_ip(ip.parse().unwrap(), allow_local, &blocked)
+        };
+
+        assert!(acceptable("1.2.3.4", false));
+        assert!(acceptable("2001:db8::1", false));
+        assert!(!acceptable("10.1.2.3", false));
+        assert!(!acceptable("::ffff:10.1.2.3", false));
+        assert!(!acceptable("0.0.0.0", true));
+        assert!(!acceptable("224.0.0.1", true));
+        assert!(!acceptable("::", true));
+
+        for local in ["127.0.0.1", "169.254.1.1", "::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!acceptable(local, false));
+            assert!(acceptable(local, true));
+        }
+        // the override doesn't apply to blocked ranges
+        assert!(!acceptable("10.1.2.3", true));
+    }
+
+    #[test]
+    fn file_logger_writes_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.log");
+        let appender = rolling_file_appender(&path, false, 1024 * 1024, None, 1).unwrap();
+        let logger =
+            AppenderLogger::new(log_builder(false, false, false).build(), Box::new(appender));
+
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Info)
+                .target("concordium_node")
+                .args(format_args!("a line for the log file"))
+                .build(),
+        );
+        // Below the configured level, so it must not be written.
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Trace)
+                .target("concordium_node")
+                .args(format_args!("a filtered line"))
+                .build(),
+        );
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("INFO: a line for the log file"));
+        assert!(!contents.contains("a filtered line"));
+    }
+
+    #[test]
+    fn bootstrap_resolution_honors_deadline() {
+        let nodes = ["fast:8888", "slow:8888", "bad:8888", "fast:9999"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        let slow_lookups = Arc::new(AtomicUsize::new(0));
+        let slow_lookups_ref = Arc::clone(&slow_lookups);
+        let resolve = move |ip_port: &str| {
+            let port = ip_port.rsplit(':').next().unwrap().parse().unwrap();
+            match ip_port.split(':').next() {
+                Some("slow") => {
+                    slow_lookups_ref.fetch_add(1, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_secs(10))
+                }
+                Some("bad") => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, "no such host"))
+                }
+                _ => {}
+            }
+            Ok(vec![SocketAddr::from(([127, 0, 0, 1], port))])
+        };
+        let mut resolver = BootstrapResolver::with_resolve(nodes.len(), resolve).unwrap();
+        let expected = vec![
+            SocketAddr::from(([127, 0, 0, 1], 8888)),
+            SocketAddr::from(([127, 0, 0, 1], 9999)),
+        ];
+
+        let start = Instant::now();
+        let resolved = resolver.resolve(&nodes, start + Duration::from_millis(200)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(resolved, expected);
+
+        // the pending lookup of the slow bootstrapper isn't repeated
+        let start = Instant::now();
+        let resolved = resolver.resolve(&nodes, start + Duration::from_millis(200)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(resolved, expected);
+        assert_eq!(slow_lookups.load(Ordering::SeqCst), 1);
+
+        assert!(resolver.resolve(&[], Instant::now()).is_err());
+    }
+}